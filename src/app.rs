@@ -1,7 +1,19 @@
-mod config;
+mod change;
+pub mod config;
+mod discovery;
+mod fuzzy;
+pub mod history;
+pub mod inventory;
+mod oui;
+pub mod probe;
+mod rows;
+pub mod schedule;
 mod statefullist;
 mod states;
+pub mod status;
+pub mod wake;
 
+use chrono::{DateTime, Local, Utc};
 use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use ratatui::{
     backend::Backend,
@@ -11,118 +23,561 @@ use ratatui::{
     widgets::*,
     Frame, Terminal,
 };
-use regex::Regex;
 use sm::{AsEnum, Initializer, Transition};
 use std::{
-    io::{self, ErrorKind},
-    str::FromStr,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
-use crate::app::{config::*, statefullist::StatefulList, states::*};
+use crate::{
+    app::{
+        change::Change,
+        config::*,
+        discovery::Discovery,
+        inventory::ImportMode,
+        oui::OuiTable,
+        probe::{Verification, VerifyOutcome},
+        rows::Row,
+        statefullist::StatefulList,
+        states::*,
+        status::StatusPoller,
+        wake::{Burst, SendJob},
+    },
+    error::{Error, Result},
+};
 
 pub struct App<'a> {
     pub state_machine: Variant,
-    pub machines: StatefulList<(String, String)>,
+    pub config_path: PathBuf,
+    pub config: config::Config,
+    pub machines: Vec<config::Machine>,
+    pub rows: StatefulList<Row>,
+    pub grouped: bool,
+    pub collapsed: HashSet<String>,
+    pub filter: String,
     pub textarea: TextArea<'a>,
     pub editing_name: String,
     pub editing_mac: String,
-    pub mac_regex: Regex,
+    pub editing_target: String,
+    pub editing_port: String,
+    pub editing_bind: String,
+    pub editing_tags: String,
+    pub editing_secureon: String,
+    pub editing_index: Option<usize>,
     pub popup_time: Option<Instant>,
+    pub verification: Option<Verification>,
+    pub status: Option<StatusPoller>,
+    pub error: Option<Error>,
+    pub read_only: bool,
+    pub send_job: Option<SendJob>,
+    pub discovery: Option<Discovery>,
+    pub importing: Vec<config::Machine>,
+    pub import_mode: ImportMode,
+    pub oui: OuiTable,
+    pub fingerprint: Option<Fingerprint>,
+    pub pending_changes: Vec<Change>,
+    pub history_entries: Vec<history::Entry>,
+    pub history: StatefulList<history::Entry>,
+    pub pending_history: Option<history::Entry>,
+    pub next_wakes: HashMap<String, DateTime<Utc>>,
 }
 
 impl<'a> App<'a> {
-    pub fn new() -> App<'a> {
+    pub fn new(config_path: PathBuf) -> App<'a> {
         let sm = states::Machine::new(Main).as_enum();
         let mut app = App {
             state_machine: sm,
-            machines: StatefulList::with_items(vec![]),
+            config_path,
+            config: config::Config::default(),
+            machines: vec![],
+            rows: StatefulList::with_items(vec![]),
+            grouped: false,
+            collapsed: HashSet::new(),
+            filter: "".into(),
             textarea: TextArea::default(),
             editing_name: "".into(),
             editing_mac: "".into(),
-            mac_regex: Regex::new(r"^([0-9A-Fa-f]{2}[:-]){5}([0-9A-Fa-f]{2})$").unwrap(),
+            editing_target: "".into(),
+            editing_port: "".into(),
+            editing_bind: "".into(),
+            editing_tags: "".into(),
+            editing_secureon: "".into(),
+            editing_index: None,
             popup_time: None,
+            verification: None,
+            status: None,
+            error: None,
+            read_only: false,
+            send_job: None,
+            discovery: None,
+            importing: vec![],
+            import_mode: ImportMode::Merge,
+            oui: OuiTable::default(),
+            fingerprint: None,
+            pending_changes: vec![],
+            history_entries: vec![],
+            history: StatefulList::with_items(vec![]),
+            pending_history: None,
+            next_wakes: HashMap::new(),
         };
-        app.load_machines().expect("Failed to load machines");
+        match app.load_machines() {
+            Ok(()) => {}
+            Err(err @ Error::ConfigParse { .. }) => {
+                app.read_only = true;
+                app.error = Some(err);
+                app.state_machine = states::Machine::new(ConfigInvalid).as_enum();
+            }
+            Err(err) => {
+                app.read_only = true;
+                app.fail(err);
+            }
+        }
         app
     }
 
-    pub fn load_machines(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = match dirs::home_dir() {
-            Some(home_dir) => Ok(home_dir.join(".wol").join("config")),
-            None => Err(Box::new(io::Error::new(
-                ErrorKind::NotFound,
-                "Home directory not found",
-            ))),
-        }?;
+    pub fn load_machines(&mut self) -> Result<()> {
+        let (mut config, fingerprint) = config::load_config(self.config_path.as_path())?;
 
-        let config = if let Ok(config) = config::read_config(config_path.as_path()) {
-            config.machines
-        } else {
-            vec![]
-        };
+        self.machines = std::mem::take(&mut config.machines);
+        self.oui = OuiTable::load(config.oui_file.as_deref());
+        self.config = config;
+        self.fingerprint = Some(fingerprint);
+        self.refresh_next_wakes();
+        self.refresh_rows();
+        self.restart_status_poller();
 
-        let machine_tuples: Vec<(String, String)> = config
-            .iter()
-            .map(|m| (m.name.clone(), m.mac_address.clone()))
-            .collect();
+        Ok(())
+    }
+
+    pub fn add_machine(&mut self, machine: config::Machine) -> Result<()> {
+        self.apply_change(Change::Add(vec![machine]))
+    }
 
-        self.machines = StatefulList::with_items(machine_tuples);
+    pub fn update_machine(&mut self, index: usize, machine: config::Machine) -> Result<()> {
+        self.apply_change(Change::update(&self.machines, index, machine))
+    }
 
-        Ok(())
+    pub fn delete_machine(&mut self, index: usize) -> Result<()> {
+        self.apply_change(Change::delete(&self.machines, index))
     }
 
-    pub fn add_machine(&mut self, name: &str, mac: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.machines
-            .items
-            .push((name.to_string(), mac.to_string()));
+    /// Applies `change` and saves. Changes stay pending until a save succeeds, so they
+    /// can be merged into a config that was changed by another program. Nothing is
+    /// changed in read-only mode, where the save could never succeed.
+    fn apply_change(&mut self, change: Change) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly(self.config_path.clone()));
+        }
+        change.apply(&mut self.machines);
+        self.pending_changes.push(change);
+        self.refresh_rows();
 
         self.save_machines()?;
 
         Ok(())
     }
 
-    pub fn delete_machine(&mut self, index: usize) -> Result<(), Box<dyn std::error::Error>> {
-        self.machines.items.remove(index);
+    /// Discards unsaved changes and reads the config again.
+    pub fn reload(&mut self) -> Result<()> {
+        self.pending_changes.clear();
+        self.load_machines()
+    }
+
+    /// Reads the config again and applies the unsaved changes on top of it.
+    pub fn merge_pending_changes(&mut self) -> Result<()> {
+        let changes = std::mem::take(&mut self.pending_changes);
+        self.load_machines()?;
+        for change in &changes {
+            change.apply(&mut self.machines);
+        }
+        self.pending_changes = changes;
+        self.refresh_rows();
 
         self.save_machines()?;
 
         Ok(())
     }
 
-    pub fn save_machines(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = match dirs::home_dir() {
-            Some(home_dir) => Ok(home_dir.join(".wol").join("config")),
-            None => Err(Box::new(io::Error::new(
-                ErrorKind::NotFound,
-                "Home directory not found",
-            ))),
-        }?;
+    pub fn save_machines(&mut self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly(self.config_path.clone()));
+        }
 
         let config = config::Config {
-            machines: self
-                .machines
-                .items
-                .iter()
-                .map(|(name, mac_address)| config::Machine {
-                    name: name.clone(),
-                    mac_address: mac_address.clone(),
-                })
-                .collect(),
+            machines: self.machines.clone(),
+            ..self.config.clone()
         };
 
-        write_config(config_path.as_path(), &config)?;
+        let path = self.config_path.as_path();
+        let fingerprint = match &self.fingerprint {
+            Some(expected) => config::save_config(path, &config, expected)?,
+            None => write_config(path, &config)?,
+        };
+        self.fingerprint = Some(fingerprint);
+        self.pending_changes.clear();
+        self.refresh_next_wakes();
+        self.refresh_rows();
+        self.restart_status_poller();
 
         Ok(())
     }
 
+    /// Shows `err` in the error popup, or asks how to resolve a config changed on disk.
+    /// Fallible actions only run from the main view.
+    pub fn fail(&mut self, err: Error) {
+        let conflict = matches!(err, Error::Conflict(_));
+        let state = match &self.state_machine {
+            InitialMain(m) if conflict => m.clone().transition(Conflict).as_enum(),
+            MainByCancel(m) if conflict => m.clone().transition(Conflict).as_enum(),
+            MainByNext(m) if conflict => m.clone().transition(Conflict).as_enum(),
+            InitialMain(m) => m.clone().transition(Fail).as_enum(),
+            MainByCancel(m) => m.clone().transition(Fail).as_enum(),
+            MainByNext(m) => m.clone().transition(Fail).as_enum(),
+            _ => return,
+        };
+        self.error = Some(err);
+        self.state_machine = state;
+    }
+
+    /// Rebuilds the rendered list, keeping the selection at the same position.
+    pub fn refresh_rows(&mut self) {
+        let rows = rows::build_rows(&self.machines, self.grouped, &self.collapsed, &self.filter);
+        let selected = match self.rows.state.selected() {
+            _ if rows.is_empty() => None,
+            Some(selected) => Some(selected.min(rows.len() - 1)),
+            None => None,
+        };
+        self.rows = StatefulList::with_items(rows);
+        self.rows.state.select(selected);
+    }
+
+    /// Index into `machines` of the selected row, if it is a machine.
+    pub fn selected_machine(&self) -> Option<usize> {
+        match self.rows.items.get(self.rows.state.selected()?)? {
+            Row::Machine { index, .. } => Some(*index),
+            Row::Group { .. } => None,
+        }
+    }
+
+    /// Name of the group the selected row belongs to in the group view.
+    pub fn selected_group(&self) -> Option<String> {
+        let row = self.rows.items.get(self.rows.state.selected()?)?;
+        row.group().map(|group| group.to_string())
+    }
+
+    /// Narrows the list to `filter` and selects the first match.
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.rows.state.select(Some(0));
+        self.refresh_rows();
+    }
+
+    pub fn toggle_grouped(&mut self) {
+        self.grouped = !self.grouped;
+        self.rows.state.select(None);
+        self.refresh_rows();
+    }
+
+    /// Collapses or expands the section when a group header is selected.
+    pub fn toggle_selected_group(&mut self) {
+        let selected = self.rows.state.selected();
+        if let Some(Row::Group { name, .. }) = selected.and_then(|i| self.rows.items.get(i)) {
+            let name = name.clone();
+            if !self.collapsed.remove(&name) {
+                self.collapsed.insert(name);
+            }
+            self.refresh_rows();
+        }
+    }
+
+    /// Starts sending to every member of `group` in the background.
+    pub fn send_group(&mut self, group: &str) {
+        let machines = rows::group_members(&self.machines, group)
+            .into_iter()
+            .map(|index| self.burst_for(index))
+            .collect();
+        self.send_job = Some(SendJob::spawn(machines));
+    }
+
+    fn burst_for(&self, index: usize) -> (config::Machine, Burst) {
+        let machine = self.machines[index].clone();
+        let burst = Burst::for_machine(&self.config, &machine);
+        (machine, burst)
+    }
+
+    /// Starts reading the neighbor tables for machines that aren't configured yet.
+    pub fn start_discovery(&mut self) {
+        let known: Vec<MacAddress> = self
+            .machines
+            .iter()
+            .map(|machine| machine.mac_address)
+            .collect();
+        self.discovery = Some(Discovery::spawn(&known));
+    }
+
+    /// Adds the selected discovery candidates as machines, renaming those whose name is
+    /// taken.
+    pub fn import_discovered(&mut self) -> Result<()> {
+        let Some(discovery) = self.discovery.take() else {
+            return Ok(());
+        };
+        let plan = discovery_plan(&self.machines, &discovery);
+        if plan.added.is_empty() {
+            return Ok(());
+        }
+        self.apply_change(Change::Add(plan.machines[self.machines.len()..].to_vec()))
+    }
+
+    /// Reads the machines to import from `path` for the preview.
+    pub fn read_import(&mut self, path: &str) -> Result<()> {
+        self.importing = inventory::read_file(Path::new(path), None)?;
+        self.import_mode = ImportMode::Merge;
+
+        Ok(())
+    }
+
+    pub fn toggle_import_mode(&mut self) {
+        self.import_mode = match self.import_mode {
+            ImportMode::Merge => ImportMode::Replace,
+            ImportMode::Replace => ImportMode::Merge,
+        };
+    }
+
+    pub fn apply_import(&mut self) -> Result<()> {
+        let imported = std::mem::take(&mut self.importing);
+        let plan = inventory::plan(&self.machines, imported, self.import_mode);
+        let change = match self.import_mode {
+            ImportMode::Merge => Change::Add(plan.machines[self.machines.len()..].to_vec()),
+            ImportMode::Replace => Change::Replace(plan.machines),
+        };
+        self.apply_change(change)
+    }
+
+    pub fn export(&self, path: &str) -> Result<()> {
+        inventory::write_file(Path::new(path), None, &self.machines)
+    }
+
+    /// Loads the wake history for the history view, newest first.
+    pub fn open_history(&mut self) -> Result<()> {
+        let mut entries = history::read(&history_path(&self.config_path))?;
+        entries.reverse();
+        self.history_entries = entries;
+        self.textarea = TextArea::default();
+        self.filter_history();
+
+        Ok(())
+    }
+
+    /// Narrows the history view to machines matching the filter input.
+    pub fn filter_history(&mut self) {
+        let filter = self.textarea.lines()[0].clone();
+        let entries = self
+            .history_entries
+            .iter()
+            .filter(|entry| {
+                filter.is_empty() || fuzzy::fuzzy_match(&filter, &entry.machine).is_some()
+            })
+            .cloned()
+            .collect();
+        self.history = StatefulList::with_items(entries);
+        if !self.history.items.is_empty() {
+            self.history.state.select(Some(0));
+        }
+    }
+
+    fn record(&self, entry: &history::Entry) {
+        // A failing history write shouldn't get in the way of waking machines.
+        let _ = history::append(&history_path(&self.config_path), entry);
+    }
+
+    /// Records the send waiting for its verification with `verification` as outcome.
+    pub fn finish_history(&mut self, verification: String) {
+        if let Some(mut entry) = self.pending_history.take() {
+            entry.verification = Some(verification);
+            self.record(&entry);
+        }
+    }
+
+    fn refresh_next_wakes(&mut self) {
+        self.next_wakes = schedule::next_wakes(&self.config.schedules, &self.machines, Utc::now());
+    }
+
+    fn restart_status_poller(&mut self) {
+        let interval = self
+            .config
+            .status_interval_secs
+            .map_or(status::DEFAULT_INTERVAL, Duration::from_secs);
+        self.status = Some(StatusPoller::spawn(&self.machines, interval));
+    }
+
+    /// Clears the input popups for a new machine.
+    pub fn start_add(&mut self) {
+        self.editing_index = None;
+        self.editing_name.clear();
+        self.editing_mac.clear();
+        self.editing_target.clear();
+        self.editing_port.clear();
+        self.editing_bind.clear();
+        self.editing_tags.clear();
+        self.editing_secureon.clear();
+        self.textarea = TextArea::default();
+    }
+
+    /// Prefills the input popups with the machine at `index`.
+    pub fn start_edit(&mut self, index: usize) {
+        let machine = &self.machines[index];
+        self.editing_name = machine.name.clone();
+        self.editing_mac = machine.mac_address.to_string();
+        self.editing_target = machine
+            .target
+            .as_ref()
+            .map(|t| t.to_string())
+            .unwrap_or_default();
+        self.editing_port = machine.port.map(|p| p.to_string()).unwrap_or_default();
+        self.editing_bind = machine.bind.clone().unwrap_or_default();
+        self.editing_tags = machine.tags.join(", ");
+        self.editing_secureon = machine
+            .secureon
+            .as_ref()
+            .map(|password| password.to_string())
+            .unwrap_or_default();
+        self.editing_index = Some(index);
+        self.textarea = prefilled(&self.editing_name);
+    }
+
+    /// Builds the machine described by the input popups, keeping settings of the
+    /// machine being edited that the popups don't cover.
+    pub fn editing_machine(&self) -> config::Machine {
+        let base = self
+            .editing_index
+            .map(|index| self.machines[index].clone())
+            .unwrap_or_default();
+        config::Machine {
+            name: self.editing_name.clone(),
+            mac_address: self.editing_mac.parse().unwrap_or_default(),
+            target: self.editing_target.parse().ok(),
+            port: self.editing_port.parse().ok(),
+            bind: Some(self.editing_bind.clone()).filter(|bind| !bind.is_empty()),
+            tags: self
+                .editing_tags
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            secureon: self.editing_secureon.parse().ok(),
+            ..base
+        }
+    }
+
+    /// Why `name` can't be used for the machine being added or edited.
+    pub fn name_error(&self, name: &str) -> Option<String> {
+        if name.trim().is_empty() {
+            return Some("name is required".into());
+        }
+        self.machines
+            .iter()
+            .enumerate()
+            .any(|(index, machine)| Some(index) != self.editing_index && machine.name == name)
+            .then(|| format!("{name} already exists"))
+    }
+
+    /// Why `mac` can't be used for a machine.
+    pub fn mac_error(&self, mac: &str) -> Option<String> {
+        match mac.parse::<MacAddress>() {
+            Ok(mac) if mac.is_unicast() => None,
+            Ok(_) => Some("broadcast, multicast and all-zero addresses can't be woken".into()),
+            Err(err) => Some(err),
+        }
+    }
+
+    /// Another machine with the same MAC, allowed after a warning.
+    pub fn duplicate_mac(&self, mac: &MacAddress) -> Option<&str> {
+        self.machines
+            .iter()
+            .enumerate()
+            .find(|(index, machine)| {
+                Some(*index) != self.editing_index && machine.mac_address == *mac
+            })
+            .map(|(_, machine)| machine.name.as_str())
+    }
+
+    /// Starts sending to the selected machine in the background.
+    pub fn send_selected(&mut self) -> bool {
+        if let Some(selected) = self.selected_machine() {
+            self.popup_time = None;
+            self.send_job = Some(SendJob::spawn(vec![self.burst_for(selected)]));
+            true
+        } else {
+            false
+        }
+    }
+
     fn on_tick(&mut self) {
+        let now = Utc::now();
+        if self.next_wakes.values().any(|next| *next <= now) {
+            self.refresh_next_wakes();
+        }
+        let verified = self.verification.as_mut().and_then(|verification| {
+            verification.poll();
+            verification
+                .outcome
+                .as_ref()
+                .map(|outcome| outcome.to_string())
+        });
+        if let Some(outcome) = verified {
+            self.finish_history(outcome);
+        }
+        let finished = self
+            .send_job
+            .as_mut()
+            .map(SendJob::poll)
+            .unwrap_or_default();
+        for index in finished {
+            let Some(job) = &self.send_job else {
+                break;
+            };
+            let sent = &job.entries[index];
+            let Some(result) = &sent.result else {
+                continue;
+            };
+            let entry = history::Entry::new(&sent.machine, "tui", result);
+            // Single sends to machines with a probe are recorded once verified.
+            let verifying = result.is_ok()
+                && sent.machine.probe.is_some()
+                && matches!(self.state_machine, SendPopBySend(_));
+            if verifying {
+                self.pending_history = Some(entry);
+            } else {
+                self.record(&entry);
+            }
+        }
+        if let Some(discovery) = &mut self.discovery {
+            discovery.poll();
+        }
         if let SendPopBySend(m) = &self.state_machine {
-            if let Some(popup_time) = self.popup_time {
-                if popup_time.elapsed() > Duration::from_secs(1) {
+            let m = m.clone();
+            let Some(job) = &mut self.send_job else {
+                return;
+            };
+            if !job.finished() {
+                return;
+            }
+            if let Some(err) = job.take_error() {
+                self.send_job = None;
+                self.error = Some(err);
+                self.state_machine = m.transition(Fail).as_enum();
+            } else if let Some(probe) = job.entries[0].machine.probe.clone() {
+                self.send_job = None;
+                self.verification = Some(Verification::spawn(probe));
+                self.state_machine = m.transition(Verify).as_enum();
+            } else {
+                let finished_at = *self.popup_time.get_or_insert_with(Instant::now);
+                if finished_at.elapsed() > Duration::from_secs(1) {
                     self.popup_time = None;
-                    self.state_machine = m.clone().transition(Next).as_enum();
+                    self.send_job = None;
+                    self.state_machine = m.transition(Next).as_enum();
                 }
             }
         }
@@ -133,25 +588,57 @@ pub fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
     tick_rate: Duration,
-) -> io::Result<()> {
+) -> Result<()> {
     let mut last_tick = Instant::now();
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        terminal
+            .draw(|f| ui(f, &mut app))
+            .map_err(Error::Terminal)?;
 
         let timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
-        if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()?.into() {
+        if crossterm::event::poll(timeout).map_err(Error::Terminal)? {
+            if let Event::Key(key) = event::read().map_err(Error::Terminal)?.into() {
                 if key.kind == KeyEventKind::Press {
                     let mut new_machine = None;
+                    let mut updated_machine = None;
                     let mut delete_machine = None;
-                    if let Some(state) = match (key, &mut app.state_machine) {
+                    let mut import_discovered = false;
+                    let mut apply_import = false;
+                    let mut reload_config = false;
+                    let mut merge_config = false;
+                    if let Some(state) = match (key, &app.state_machine) {
                         (input, NameInputByAdd(m)) => match input.code {
                             KeyCode::Enter => {
-                                app.editing_name = app.textarea.lines()[0].clone();
+                                let name = app.textarea.lines()[0].trim().to_string();
+                                if app.name_error(&name).is_none() {
+                                    app.editing_name = name;
+                                    app.textarea = prefilled(&app.editing_mac);
+                                    Some(m.clone().transition(Next).as_enum())
+                                } else {
+                                    None
+                                }
+                            }
+                            KeyCode::Esc => {
                                 app.textarea = TextArea::default();
-                                Some(m.clone().transition(Next).as_enum())
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, NameInputByEdit(m)) => match input.code {
+                            KeyCode::Enter => {
+                                let name = app.textarea.lines()[0].trim().to_string();
+                                if app.name_error(&name).is_none() {
+                                    app.editing_name = name;
+                                    app.textarea = prefilled(&app.editing_mac);
+                                    Some(m.clone().transition(Next).as_enum())
+                                } else {
+                                    None
+                                }
                             }
                             KeyCode::Esc => {
                                 app.textarea = TextArea::default();
@@ -165,8 +652,27 @@ pub fn run_app<B: Backend>(
                         (input, MacInputByNext(m)) => match input.code {
                             KeyCode::Enter => {
                                 app.editing_mac = app.textarea.lines()[0].clone();
-                                if app.mac_regex.is_match(&app.editing_mac) {
-                                    app.textarea = TextArea::default();
+                                if app.mac_error(&app.editing_mac).is_none() {
+                                    app.textarea = prefilled(&app.editing_target);
+                                    Some(m.clone().transition(Next).as_enum())
+                                } else {
+                                    None
+                                }
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, TargetInputByNext(m)) => match input.code {
+                            KeyCode::Enter => {
+                                app.editing_target = app.textarea.lines()[0].clone();
+                                if is_valid_target(&app.editing_target) {
+                                    app.textarea = prefilled(&app.editing_port);
                                     Some(m.clone().transition(Next).as_enum())
                                 } else {
                                     None
@@ -181,100 +687,427 @@ pub fn run_app<B: Backend>(
                                 None
                             }
                         },
+                        (input, PortInputByNext(m)) => match input.code {
+                            KeyCode::Enter => {
+                                app.editing_port = app.textarea.lines()[0].clone();
+                                if is_valid_port(&app.editing_port) {
+                                    app.textarea = prefilled(&app.editing_bind);
+                                    Some(m.clone().transition(Next).as_enum())
+                                } else {
+                                    None
+                                }
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, BindInputByNext(m)) => match input.code {
+                            KeyCode::Enter => {
+                                app.editing_bind = app.textarea.lines()[0].trim().to_string();
+                                app.textarea = prefilled(&app.editing_tags);
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, TagsInputByNext(m)) => match input.code {
+                            KeyCode::Enter => {
+                                app.editing_tags = app.textarea.lines()[0].clone();
+                                app.textarea = prefilled(&app.editing_secureon);
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, SecureOnInputByNext(m)) => match input.code {
+                            KeyCode::Enter => {
+                                app.editing_secureon = app.textarea.lines()[0].trim().to_string();
+                                if is_valid_secureon(&app.editing_secureon) {
+                                    app.textarea = TextArea::default();
+                                    if app.editing_index.is_some() {
+                                        Some(m.clone().transition(Edit).as_enum())
+                                    } else {
+                                        Some(m.clone().transition(Next).as_enum())
+                                    }
+                                } else {
+                                    None
+                                }
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, DiscoverNameByEdit(m)) => match input.code {
+                            KeyCode::Enter => {
+                                let name = app.textarea.lines()[0].trim().to_string();
+                                if let Some(candidate) =
+                                    app.discovery.as_mut().and_then(|d| d.selected_candidate())
+                                {
+                                    candidate.name = name;
+                                }
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, ImportPathByImport(m)) => match input.code {
+                            KeyCode::Enter => {
+                                let m = m.clone();
+                                let path = app.textarea.lines()[0].trim().to_string();
+                                app.textarea = TextArea::default();
+                                match app.read_import(&path) {
+                                    Ok(()) => Some(m.transition(Import).as_enum()),
+                                    Err(err) => {
+                                        app.error = Some(err);
+                                        Some(m.transition(Fail).as_enum())
+                                    }
+                                }
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, ExportPathByExport(m)) => match input.code {
+                            KeyCode::Enter => {
+                                let m = m.clone();
+                                let path = app.textarea.lines()[0].trim().to_string();
+                                app.textarea = TextArea::default();
+                                match app.export(&path) {
+                                    Ok(()) => Some(m.transition(Next).as_enum()),
+                                    Err(err) => {
+                                        app.error = Some(err);
+                                        Some(m.transition(Fail).as_enum())
+                                    }
+                                }
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                None
+                            }
+                        },
+                        (input, HistoryViewByHistory(m)) => match input.code {
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            KeyCode::Enter => None,
+                            KeyCode::Down => {
+                                app.history.next();
+                                None
+                            }
+                            KeyCode::Up => {
+                                app.history.previous();
+                                None
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                app.filter_history();
+                                None
+                            }
+                        },
+                        (input, FilterBySearch(m)) => match input.code {
+                            KeyCode::Enter => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            KeyCode::Esc => {
+                                app.textarea = TextArea::default();
+                                let m = m.clone();
+                                app.set_filter("".into());
+                                Some(m.transition(Cancel).as_enum())
+                            }
+                            KeyCode::Down => {
+                                app.rows.next();
+                                None
+                            }
+                            KeyCode::Up => {
+                                app.rows.previous();
+                                None
+                            }
+                            _ => {
+                                app.textarea.input(input);
+                                let filter = app.textarea.lines()[0].clone();
+                                if filter != app.filter {
+                                    app.set_filter(filter);
+                                }
+                                None
+                            }
+                        },
                         (input, state) => match (input.code, state) {
+                            (KeyCode::Char('Y'), InitialConfigInvalid(m)) => {
+                                app.error = None;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Char('n'), InitialConfigInvalid(_))
+                            | (KeyCode::Char('N'), InitialConfigInvalid(_))
+                            | (KeyCode::Char('q'), InitialConfigInvalid(_))
+                            | (KeyCode::Esc, InitialConfigInvalid(_)) => return Ok(()),
                             (KeyCode::Char('q'), InitialMain(_))
                             | (KeyCode::Char('q'), MainByCancel(_))
                             | (KeyCode::Char('q'), MainByNext(_)) => return Ok(()),
                             (KeyCode::Down, InitialMain(_))
                             | (KeyCode::Down, MainByCancel(_))
                             | (KeyCode::Down, MainByNext(_)) => {
-                                app.machines.next();
+                                app.rows.next();
+                                None
+                            }
+                            (KeyCode::Up, InitialMain(_))
+                            | (KeyCode::Up, MainByCancel(_))
+                            | (KeyCode::Up, MainByNext(_)) => {
+                                app.rows.previous();
+                                None
+                            }
+                            (KeyCode::Enter, InitialMain(m)) => {
+                                let m = m.clone();
+                                if app.send_selected() {
+                                    Some(m.transition(Send).as_enum())
+                                } else {
+                                    app.toggle_selected_group();
+                                    None
+                                }
+                            }
+                            (KeyCode::Enter, MainByNext(m)) => {
+                                let m = m.clone();
+                                if app.send_selected() {
+                                    Some(m.transition(Send).as_enum())
+                                } else {
+                                    app.toggle_selected_group();
+                                    None
+                                }
+                            }
+                            (KeyCode::Enter, MainByCancel(m)) => {
+                                let m = m.clone();
+                                if app.send_selected() {
+                                    Some(m.transition(Send).as_enum())
+                                } else {
+                                    app.toggle_selected_group();
+                                    None
+                                }
+                            }
+                            (KeyCode::Char('/'), InitialMain(m)) => {
+                                let m = m.clone();
+                                app.textarea = prefilled(&app.filter);
+                                Some(m.transition(Search).as_enum())
+                            }
+                            (KeyCode::Char('/'), MainByCancel(m)) => {
+                                let m = m.clone();
+                                app.textarea = prefilled(&app.filter);
+                                Some(m.transition(Search).as_enum())
+                            }
+                            (KeyCode::Char('/'), MainByNext(m)) => {
+                                let m = m.clone();
+                                app.textarea = prefilled(&app.filter);
+                                Some(m.transition(Search).as_enum())
+                            }
+                            (KeyCode::Esc, InitialMain(_))
+                            | (KeyCode::Esc, MainByCancel(_))
+                            | (KeyCode::Esc, MainByNext(_)) => {
+                                app.set_filter("".into());
+                                None
+                            }
+                            (KeyCode::Char('g'), InitialMain(_))
+                            | (KeyCode::Char('g'), MainByCancel(_))
+                            | (KeyCode::Char('g'), MainByNext(_)) => {
+                                app.toggle_grouped();
+                                None
+                            }
+                            (KeyCode::Char(' '), InitialMain(_))
+                            | (KeyCode::Char(' '), MainByCancel(_))
+                            | (KeyCode::Char(' '), MainByNext(_)) => {
+                                app.toggle_selected_group();
+                                None
+                            }
+                            (KeyCode::Char('W'), InitialMain(m)) => {
+                                if let Some(group) = app.selected_group() {
+                                    let m = m.clone();
+                                    app.send_group(&group);
+                                    Some(m.transition(WakeGroup).as_enum())
+                                } else {
+                                    None
+                                }
+                            }
+                            (KeyCode::Char('W'), MainByCancel(m)) => {
+                                if let Some(group) = app.selected_group() {
+                                    let m = m.clone();
+                                    app.send_group(&group);
+                                    Some(m.transition(WakeGroup).as_enum())
+                                } else {
+                                    None
+                                }
+                            }
+                            (KeyCode::Char('W'), MainByNext(m)) => {
+                                if let Some(group) = app.selected_group() {
+                                    let m = m.clone();
+                                    app.send_group(&group);
+                                    Some(m.transition(WakeGroup).as_enum())
+                                } else {
+                                    None
+                                }
+                            }
+                            (
+                                KeyCode::Char('a' | 'e' | 'd' | 's'),
+                                InitialMain(_) | MainByCancel(_) | MainByNext(_),
+                            ) if app.read_only => {
+                                app.fail(Error::ReadOnly(app.config_path.clone()));
                                 None
                             }
-                            (KeyCode::Up, InitialMain(_))
-                            | (KeyCode::Up, MainByCancel(_))
-                            | (KeyCode::Up, MainByNext(_)) => {
-                                app.machines.previous();
-                                None
+                            (KeyCode::Char('s'), InitialMain(m)) => {
+                                let m = m.clone();
+                                app.start_discovery();
+                                Some(m.transition(Discover).as_enum())
+                            }
+                            (KeyCode::Char('s'), MainByCancel(m)) => {
+                                let m = m.clone();
+                                app.start_discovery();
+                                Some(m.transition(Discover).as_enum())
+                            }
+                            (KeyCode::Char('s'), MainByNext(m)) => {
+                                let m = m.clone();
+                                app.start_discovery();
+                                Some(m.transition(Discover).as_enum())
+                            }
+                            (KeyCode::Char('m'), InitialMain(m)) => {
+                                Some(m.clone().transition(Transfer).as_enum())
+                            }
+                            (KeyCode::Char('m'), MainByCancel(m)) => {
+                                Some(m.clone().transition(Transfer).as_enum())
+                            }
+                            (KeyCode::Char('m'), MainByNext(m)) => {
+                                Some(m.clone().transition(Transfer).as_enum())
+                            }
+                            (KeyCode::Char('h'), InitialMain(m)) => {
+                                let m = m.clone();
+                                match app.open_history() {
+                                    Ok(()) => Some(m.transition(History).as_enum()),
+                                    Err(err) => {
+                                        app.error = Some(err);
+                                        Some(m.transition(Fail).as_enum())
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('h'), MainByCancel(m)) => {
+                                let m = m.clone();
+                                match app.open_history() {
+                                    Ok(()) => Some(m.transition(History).as_enum()),
+                                    Err(err) => {
+                                        app.error = Some(err);
+                                        Some(m.transition(Fail).as_enum())
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('h'), MainByNext(m)) => {
+                                let m = m.clone();
+                                match app.open_history() {
+                                    Ok(()) => Some(m.transition(History).as_enum()),
+                                    Err(err) => {
+                                        app.error = Some(err);
+                                        Some(m.transition(Fail).as_enum())
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('a'), InitialMain(m)) => {
+                                let m = m.clone();
+                                app.start_add();
+                                Some(m.transition(Add).as_enum())
+                            }
+                            (KeyCode::Char('a'), MainByNext(m)) => {
+                                let m = m.clone();
+                                app.start_add();
+                                Some(m.transition(Add).as_enum())
                             }
-                            (KeyCode::Enter, InitialMain(m)) => {
-                                if let Some(selected) = app.machines.state.selected() {
-                                    let (_, mac) = app.machines.items[selected].clone();
-                                    wol::send_wol(
-                                        wol::MacAddr::from_str(mac.as_str()).unwrap(),
-                                        None,
-                                        None,
-                                    )
-                                    .unwrap();
-                                    app.popup_time = Some(Instant::now());
-                                    Some(m.clone().transition(Send).as_enum())
+                            (KeyCode::Char('a'), MainByCancel(m)) => {
+                                let m = m.clone();
+                                app.start_add();
+                                Some(m.transition(Add).as_enum())
+                            }
+                            (KeyCode::Char('e'), InitialMain(m)) => {
+                                if let Some(selected) = app.selected_machine() {
+                                    let m = m.clone();
+                                    app.start_edit(selected);
+                                    Some(m.transition(Edit).as_enum())
                                 } else {
                                     None
                                 }
                             }
-                            (KeyCode::Enter, MainByNext(m)) => {
-                                if let Some(selected) = app.machines.state.selected() {
-                                    let (_, mac) = app.machines.items[selected].clone();
-                                    wol::send_wol(
-                                        wol::MacAddr::from_str(mac.as_str()).unwrap(),
-                                        None,
-                                        None,
-                                    )
-                                    .unwrap();
-                                    app.popup_time = Some(Instant::now());
-                                    Some(m.clone().transition(Send).as_enum())
+                            (KeyCode::Char('e'), MainByCancel(m)) => {
+                                if let Some(selected) = app.selected_machine() {
+                                    let m = m.clone();
+                                    app.start_edit(selected);
+                                    Some(m.transition(Edit).as_enum())
                                 } else {
                                     None
                                 }
                             }
-                            (KeyCode::Enter, MainByCancel(m)) => {
-                                if let Some(selected) = app.machines.state.selected() {
-                                    let (_, mac) = app.machines.items[selected].clone();
-                                    wol::send_wol(
-                                        wol::MacAddr::from_str(mac.as_str()).unwrap(),
-                                        None,
-                                        None,
-                                    )
-                                    .unwrap();
-                                    app.popup_time = Some(Instant::now());
-                                    Some(m.clone().transition(Send).as_enum())
+                            (KeyCode::Char('e'), MainByNext(m)) => {
+                                if let Some(selected) = app.selected_machine() {
+                                    let m = m.clone();
+                                    app.start_edit(selected);
+                                    Some(m.transition(Edit).as_enum())
                                 } else {
                                     None
                                 }
                             }
-                            (KeyCode::Char('a'), InitialMain(m)) => {
-                                Some(m.clone().transition(Add).as_enum())
-                            }
-                            (KeyCode::Char('a'), MainByNext(m)) => {
-                                Some(m.clone().transition(Add).as_enum())
-                            }
-                            (KeyCode::Char('a'), MainByCancel(m)) => {
-                                Some(m.clone().transition(Add).as_enum())
-                            }
                             (KeyCode::Char('d'), InitialMain(m)) => {
-                                if app.machines.state.selected().is_some() {
+                                if app.selected_machine().is_some() {
                                     Some(m.clone().transition(Delete).as_enum())
                                 } else {
                                     None
                                 }
                             }
                             (KeyCode::Char('d'), MainByCancel(m)) => {
-                                if app.machines.state.selected().is_some() {
+                                if app.selected_machine().is_some() {
                                     Some(m.clone().transition(Delete).as_enum())
                                 } else {
                                     None
                                 }
                             }
                             (KeyCode::Char('d'), MainByNext(m)) => {
-                                if app.machines.state.selected().is_some() {
+                                if app.selected_machine().is_some() {
                                     Some(m.clone().transition(Delete).as_enum())
                                 } else {
                                     None
                                 }
                             }
                             (KeyCode::Char('Y'), ConfirmAddByNext(m)) => {
-                                new_machine =
-                                    Some((app.editing_name.clone(), app.editing_mac.clone()));
+                                new_machine = Some(app.editing_machine());
                                 Some(m.clone().transition(Next).as_enum())
                             }
                             (KeyCode::Char('n'), ConfirmAddByNext(m))
@@ -282,9 +1115,19 @@ pub fn run_app<B: Backend>(
                             | (KeyCode::Esc, ConfirmAddByNext(m)) => {
                                 Some(m.clone().transition(Cancel).as_enum())
                             }
+                            (KeyCode::Char('Y'), ConfirmEditByEdit(m)) => {
+                                updated_machine = app
+                                    .editing_index
+                                    .map(|index| (index, app.editing_machine()));
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Char('n'), ConfirmEditByEdit(m))
+                            | (KeyCode::Char('N'), ConfirmEditByEdit(m))
+                            | (KeyCode::Esc, ConfirmEditByEdit(m)) => {
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
                             (KeyCode::Char('Y'), ConfirmDeleteByDelete(m)) => {
-                                delete_machine = Some(app.machines.state.selected().unwrap());
-                                app.machines.previous();
+                                delete_machine = app.selected_machine();
                                 Some(m.clone().transition(Next).as_enum())
                             }
                             (KeyCode::Char('n'), ConfirmDeleteByDelete(m))
@@ -292,17 +1135,179 @@ pub fn run_app<B: Backend>(
                             | (KeyCode::Esc, ConfirmDeleteByDelete(m)) => {
                                 Some(m.clone().transition(Cancel).as_enum())
                             }
+                            (KeyCode::Enter, ErrorPopByFail(m)) => {
+                                app.error = None;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Esc, ErrorPopByFail(m))
+                            | (KeyCode::Char('q'), ErrorPopByFail(m)) => {
+                                app.error = None;
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Enter, GroupSendPopByWakeGroup(m)) => {
+                                app.send_job = None;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Esc, GroupSendPopByWakeGroup(m))
+                            | (KeyCode::Char('q'), GroupSendPopByWakeGroup(m)) => {
+                                app.send_job = None;
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Down, DiscoveringByDiscover(_))
+                            | (KeyCode::Down, DiscoveringByNext(_))
+                            | (KeyCode::Down, DiscoveringByCancel(_)) => {
+                                if let Some(discovery) = &mut app.discovery {
+                                    discovery.candidates.next();
+                                }
+                                None
+                            }
+                            (KeyCode::Up, DiscoveringByDiscover(_))
+                            | (KeyCode::Up, DiscoveringByNext(_))
+                            | (KeyCode::Up, DiscoveringByCancel(_)) => {
+                                if let Some(discovery) = &mut app.discovery {
+                                    discovery.candidates.previous();
+                                }
+                                None
+                            }
+                            (KeyCode::Char(' '), DiscoveringByDiscover(_))
+                            | (KeyCode::Char(' '), DiscoveringByNext(_))
+                            | (KeyCode::Char(' '), DiscoveringByCancel(_)) => {
+                                if let Some(discovery) = &mut app.discovery {
+                                    discovery.toggle_selected();
+                                }
+                                None
+                            }
+                            (KeyCode::Char('e'), DiscoveringByDiscover(m)) => {
+                                match app.discovery.as_mut().and_then(|d| d.selected_candidate()) {
+                                    Some(candidate) => {
+                                        app.textarea = prefilled(&candidate.name);
+                                        Some(m.clone().transition(Edit).as_enum())
+                                    }
+                                    None => None,
+                                }
+                            }
+                            (KeyCode::Char('e'), DiscoveringByNext(m)) => {
+                                match app.discovery.as_mut().and_then(|d| d.selected_candidate()) {
+                                    Some(candidate) => {
+                                        app.textarea = prefilled(&candidate.name);
+                                        Some(m.clone().transition(Edit).as_enum())
+                                    }
+                                    None => None,
+                                }
+                            }
+                            (KeyCode::Char('e'), DiscoveringByCancel(m)) => {
+                                match app.discovery.as_mut().and_then(|d| d.selected_candidate()) {
+                                    Some(candidate) => {
+                                        app.textarea = prefilled(&candidate.name);
+                                        Some(m.clone().transition(Edit).as_enum())
+                                    }
+                                    None => None,
+                                }
+                            }
+                            (KeyCode::Enter, DiscoveringByDiscover(m)) => {
+                                import_discovered = true;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Enter, DiscoveringByNext(m)) => {
+                                import_discovered = true;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Enter, DiscoveringByCancel(m)) => {
+                                import_discovered = true;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Esc, DiscoveringByDiscover(m))
+                            | (KeyCode::Char('q'), DiscoveringByDiscover(m)) => {
+                                app.discovery = None;
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Esc, DiscoveringByNext(m))
+                            | (KeyCode::Char('q'), DiscoveringByNext(m)) => {
+                                app.discovery = None;
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Esc, DiscoveringByCancel(m))
+                            | (KeyCode::Char('q'), DiscoveringByCancel(m)) => {
+                                app.discovery = None;
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Char('i'), TransferMenuByTransfer(m)) => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Import).as_enum())
+                            }
+                            (KeyCode::Char('x'), TransferMenuByTransfer(m)) => {
+                                app.textarea = TextArea::default();
+                                Some(m.clone().transition(Export).as_enum())
+                            }
+                            (KeyCode::Esc, TransferMenuByTransfer(m))
+                            | (KeyCode::Char('q'), TransferMenuByTransfer(m)) => {
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Char('m'), ImportPreviewByImport(_)) => {
+                                app.toggle_import_mode();
+                                None
+                            }
+                            (KeyCode::Char('Y'), ImportPreviewByImport(m)) => {
+                                apply_import = true;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Char('n'), ImportPreviewByImport(m))
+                            | (KeyCode::Char('N'), ImportPreviewByImport(m))
+                            | (KeyCode::Esc, ImportPreviewByImport(m)) => {
+                                app.importing.clear();
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Char('m'), ConflictPopByConflict(m)) => {
+                                merge_config = true;
+                                app.error = None;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Char('r'), ConflictPopByConflict(m)) => {
+                                reload_config = true;
+                                app.error = None;
+                                Some(m.clone().transition(Next).as_enum())
+                            }
+                            (KeyCode::Esc, ConflictPopByConflict(m)) => {
+                                app.error = None;
+                                Some(m.clone().transition(Cancel).as_enum())
+                            }
+                            (KeyCode::Enter, VerifyingByVerify(m)) => {
+                                let m = m.clone();
+                                app.verification = None;
+                                app.finish_history("not waited for".into());
+                                Some(m.transition(Next).as_enum())
+                            }
+                            (KeyCode::Esc, VerifyingByVerify(m))
+                            | (KeyCode::Char('q'), VerifyingByVerify(m)) => {
+                                let m = m.clone();
+                                app.verification = None;
+                                app.finish_history("not waited for".into());
+                                Some(m.transition(Cancel).as_enum())
+                            }
                             _ => None,
                         },
                     } {
                         app.state_machine = state;
                     }
-                    if let Some((name, mac)) = new_machine {
-                        app.add_machine(name.as_str(), mac.as_str())
-                            .expect("cannot add machine");
-                    }
-                    if let Some(index) = delete_machine {
-                        app.delete_machine(index).expect("can not delete machine");
+                    let result = if let Some(machine) = new_machine {
+                        app.add_machine(machine)
+                    } else if let Some((index, machine)) = updated_machine {
+                        app.update_machine(index, machine)
+                    } else if let Some(index) = delete_machine {
+                        app.delete_machine(index)
+                    } else if import_discovered {
+                        app.import_discovered()
+                    } else if apply_import {
+                        app.apply_import()
+                    } else if reload_config {
+                        app.reload()
+                    } else if merge_config {
+                        app.merge_pending_changes()
+                    } else {
+                        Ok(())
+                    };
+                    if let Err(err) = result {
+                        app.fail(err);
                     }
                 }
             }
@@ -321,20 +1326,68 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .split(f.size());
 
     let items: Vec<ListItem> = app
-        .machines
+        .rows
         .items
         .iter()
-        .map(|(name, mac)| {
-            let lines = Spans::from(vec![
-                Span::from(format!("{:<20}", name)),
-                Span::from(mac.as_str()),
-            ]);
-            ListItem::new(lines).style(Style::default().fg(Color::Black).bg(Color::White))
+        .map(|row| match row {
+            Row::Group {
+                name,
+                members,
+                collapsed,
+            } => {
+                let marker = if *collapsed { "▸" } else { "▾" };
+                let lines = Spans::from(Span::styled(
+                    format!("{marker} {name} ({members})"),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ));
+                ListItem::new(lines).style(Style::default().fg(Color::Black).bg(Color::Gray))
+            }
+            Row::Machine { index, group } => {
+                let machine = &app.machines[*index];
+                let indent = if group.is_some() { "  " } else { "" };
+                let mut spans = vec![
+                    Span::from(indent),
+                    status_span(app.status.as_ref(), machine),
+                ];
+                spans.extend(highlighted(&machine.name, &app.filter, 20));
+                spans.extend(highlighted(
+                    &machine.mac_address.to_string(),
+                    &app.filter,
+                    20,
+                ));
+                if let Some(vendor) = app.oui.describe(&machine.mac_address) {
+                    let vendor: String = vendor.chars().take(22).collect();
+                    spans.push(Span::styled(
+                        format!("{vendor:<24}"),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                spans.push(Span::from(format!("{:<24}", wake::target_label(machine))));
+                if let Some(next) = app.next_wakes.get(&machine.name) {
+                    spans.push(Span::styled(
+                        format!(
+                            "next {}",
+                            next.with_timezone(&Local).format("%a %d %b %H:%M")
+                        ),
+                        Style::default().fg(Color::Blue),
+                    ));
+                }
+                let lines = Spans::from(spans);
+                ListItem::new(lines).style(Style::default().fg(Color::Black).bg(Color::White))
+            }
         })
         .collect();
 
     let items = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Machines"))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Machines{}{}",
+            if app.read_only { " [read-only]" } else { "" },
+            if app.filter.is_empty() {
+                "".to_string()
+            } else {
+                format!(" /{}", app.filter)
+            }
+        )))
         .highlight_style(
             Style::default()
                 .bg(Color::LightGreen)
@@ -343,35 +1396,95 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .highlight_symbol(">> ");
 
     // We can now render the item list
-    f.render_stateful_widget(items, chunks[0], &mut app.machines.state);
+    f.render_stateful_widget(items, chunks[0], &mut app.rows.state);
 
-    let keymap_line = Spans::from(Span::raw("Select [↑↓] Send[Enter] Add[a] Delete[d] Quit[q]"));
-    f.render_widget(
-        Paragraph::new(keymap_line).block(Block::default()),
-        chunks[1],
-    );
+    if let FilterBySearch(_) = &app.state_machine {
+        app.textarea.set_block(
+            Block::default().title("Filter by name, MAC or group [Enter: keep, Esc: clear]"),
+        );
+        f.render_widget(app.textarea.widget(), chunks[1]);
+    } else {
+        let keymap_line = Spans::from(Span::raw(
+            "Select [↑↓] Send[Enter] Add[a] Edit[e] Delete[d] Filter[/] Groups[g] Fold[Space] Wake group[W] Discover[s] Import/Export[m] History[h] Quit[q]",
+        ));
+        f.render_widget(
+            Paragraph::new(keymap_line)
+                .block(Block::default())
+                .wrap(Wrap { trim: true }),
+            chunks[1],
+        );
+    }
 
     match &app.state_machine {
-        NameInputByAdd(_) => {
-            app.textarea
-                .set_block(Block::default().borders(Borders::ALL).title("Machine Name"));
+        NameInputByAdd(_) | NameInputByEdit(_) => {
+            let error = app.name_error(app.textarea.lines()[0].trim());
+            let style = validation_style(error.is_none());
+            let title = match error {
+                Some(error) => format!("Machine Name: {error}"),
+                None => "Machine Name".to_string(),
+            };
+            app.textarea.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .style(style),
+            );
             let widget = app.textarea.widget();
             let area = centered_rect(60, 3, f.size());
             f.render_widget(Clear, area);
             f.render_widget(widget, area);
         }
         MacInputByNext(_) => {
-            let style = if app.mac_regex.is_match(app.textarea.lines()[0].as_str()) {
-                Style::default()
-                    .fg(Color::Green)
-                    .add_modifier(Modifier::BOLD)
-            } else {
-                Style::default().fg(Color::Red)
+            let input = app.textarea.lines()[0].as_str();
+            let (title, style) = match (app.mac_error(input), input.parse::<MacAddress>()) {
+                (Some(error), _) => (format!("MAC Address: {error}"), validation_style(false)),
+                (None, Ok(mac)) => match app.duplicate_mac(&mac) {
+                    Some(name) => (
+                        format!("MAC Address: also used by {name}"),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    None => (
+                        match app.oui.describe(&mac) {
+                            Some(vendor) => format!("MAC Address: {vendor}"),
+                            None => "MAC Address".to_string(),
+                        },
+                        validation_style(true),
+                    ),
+                },
+                (None, Err(_)) => ("MAC Address".to_string(), validation_style(false)),
             };
             app.textarea.set_block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title("MAC Address")
+                    .title(title)
+                    .style(style),
+            );
+            let widget = app.textarea.widget();
+            let area = centered_rect(60, 3, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(widget, area);
+        }
+        TargetInputByNext(_) => {
+            let style = validation_style(is_valid_target(app.textarea.lines()[0].as_str()));
+            app.textarea.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(
+                        "Target Address, IPv4 or IPv6 like ff02::1%eth0 (empty: 255.255.255.255)",
+                    )
+                    .style(style),
+            );
+            let widget = app.textarea.widget();
+            let area = centered_rect(60, 3, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(widget, area);
+        }
+        PortInputByNext(_) => {
+            let style = validation_style(is_valid_port(app.textarea.lines()[0].as_str()));
+            app.textarea.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("UDP Port (empty: 9)")
                     .style(style),
             );
             let widget = app.textarea.widget();
@@ -379,23 +1492,115 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             f.render_widget(Clear, area);
             f.render_widget(widget, area);
         }
+        BindInputByNext(_) => {
+            app.textarea.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Bind Address or Interface (empty: default)"),
+            );
+            let widget = app.textarea.widget();
+            let area = centered_rect(60, 3, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(widget, area);
+        }
+        TagsInputByNext(_) => {
+            app.textarea.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Groups, comma separated (empty: none)"),
+            );
+            let widget = app.textarea.widget();
+            let area = centered_rect(60, 3, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(widget, area);
+        }
+        SecureOnInputByNext(_) => {
+            let password = app.textarea.lines()[0].as_str();
+            let style = validation_style(is_valid_secureon(password));
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("SecureOn Password, e.g. aa:bb:cc:dd (empty: none)")
+                .style(style);
+            let masked = "*".repeat(password.chars().count());
+            let area = centered_rect(60, 3, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(Paragraph::new(masked).block(block), area);
+        }
         ConfirmAddByNext(_) => {
-            let block = Block::default().borders(Borders::ALL);
+            let machine = app.editing_machine();
+            let duplicate = app.duplicate_mac(&machine.mac_address);
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .style(duplicate_style(duplicate));
             let text = format!(
-                "name: {}\nMAC:  {}\n\nAdd new machine? (Y/n)",
-                app.editing_name, app.editing_mac
+                "name:   {}\nMAC:    {}\ntarget: {}\nbind:   {}\ngroups: {}\nsecure: {}\n{}\nAdd new machine? (Y/n)",
+                machine.name,
+                mac_label(&app.oui, &machine.mac_address),
+                wake::target_label(&machine),
+                machine.bind.as_deref().unwrap_or("default"),
+                machine.tags.join(", "),
+                if machine.secureon.is_some() { "set" } else { "none" },
+                duplicate_warning(duplicate),
             );
             let paragraph = Paragraph::new(text);
-            let area = centered_rect(60, 6, f.size());
+            let area = centered_rect(60, 11, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        ConfirmEditByEdit(_) => {
+            let index = app.editing_index.unwrap_or_default();
+            let old = &app.machines[index];
+            let new = app.editing_machine();
+            let duplicate = app.duplicate_mac(&new.mac_address);
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .style(duplicate_style(duplicate));
+            let mut lines = vec![
+                diff_line("name:  ", &old.name, &new.name),
+                diff_line(
+                    "MAC:   ",
+                    &mac_label(&app.oui, &old.mac_address),
+                    &mac_label(&app.oui, &new.mac_address),
+                ),
+                diff_line(
+                    "target:",
+                    &wake::target_label(old),
+                    &wake::target_label(&new),
+                ),
+                diff_line(
+                    "bind:  ",
+                    old.bind.as_deref().unwrap_or("default"),
+                    new.bind.as_deref().unwrap_or("default"),
+                ),
+                diff_line("groups:", &old.tags.join(", "), &new.tags.join(", ")),
+                diff_line(
+                    "secure:",
+                    if old.secureon.is_some() {
+                        "set"
+                    } else {
+                        "none"
+                    },
+                    match &new.secureon {
+                        None => "none",
+                        Some(_) if new.secureon == old.secureon => "set",
+                        Some(_) => "changed",
+                    },
+                ),
+            ];
+            lines.push(Spans::from(duplicate_warning(duplicate)));
+            lines.push(Spans::from("Save changes? (Y/n)"));
+            let paragraph = Paragraph::new(lines);
+            let area = centered_rect(60, 11, f.size());
             f.render_widget(Clear, area);
             f.render_widget(paragraph.block(block), area);
         }
         ConfirmDeleteByDelete(_) => {
             let block = Block::default().borders(Borders::ALL);
-            let selected = app.machines.state.selected().unwrap_or_default();
+            let selected = app.selected_machine().unwrap_or_default();
             let text = format!(
                 "name: {}\nMAC:  {}\n\nDelete machine? (Y/n)",
-                app.machines.items[selected].0, app.machines.items[selected].1
+                app.machines[selected].name,
+                mac_label(&app.oui, &app.machines[selected].mac_address)
             );
             let paragraph = Paragraph::new(text);
             let area = centered_rect(60, 6, f.size());
@@ -407,10 +1612,319 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                 .fg(Color::Green)
                 .add_modifier(Modifier::BOLD);
             let block = Block::default().borders(Borders::ALL).style(style);
-            let selected = app.machines.state.selected().unwrap_or_default();
+            let text = match app.send_job.as_ref().and_then(|job| job.entries.first()) {
+                Some(entry) => {
+                    let progress = if entry.result.is_some() {
+                        format!("Sent {} wol packets to", entry.total)
+                    } else {
+                        format!("{}/{} packets sent to", entry.sent, entry.total)
+                    };
+                    format!(
+                        "name: {}\nMAC:  {}\n\n{} {}",
+                        entry.machine.name,
+                        entry.machine.mac_address,
+                        progress,
+                        wake::target_label(&entry.machine)
+                    )
+                }
+                None => String::new(),
+            };
+            let paragraph = Paragraph::new(text);
+            let area = centered_rect(60, 6, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        InitialConfigInvalid(_) => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Invalid Config")
+                .style(Style::default().fg(Color::Red));
+            let text = format!(
+                "{}\n\nOpen read-only? Changes will not be saved. (Y/n)",
+                app.error
+                    .as_ref()
+                    .map(|err| err.to_string())
+                    .unwrap_or_default()
+            );
+            let paragraph = Paragraph::new(text).wrap(Wrap { trim: true });
+            let area = centered_rect(60, 8, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        ConflictPopByConflict(_) => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Config Changed")
+                .style(Style::default().fg(Color::Yellow));
+            let text = format!(
+                "{}\n\n[m] merge: apply your unsaved changes on top\n[r] reload: discard your unsaved changes\n[Esc] keep editing without saving",
+                app.error
+                    .as_ref()
+                    .map(|err| err.to_string())
+                    .unwrap_or_default()
+            );
+            let paragraph = Paragraph::new(text).wrap(Wrap { trim: true });
+            let area = centered_rect(60, 9, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        HistoryViewByHistory(_) => {
+            let area = centered_rect(90, f.size().height.saturating_sub(4), f.size());
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+                .split(area);
+            let items: Vec<ListItem> = app
+                .history
+                .items
+                .iter()
+                .map(|entry| {
+                    let style = match &entry.error {
+                        Some(_) => Style::default().fg(Color::Red),
+                        None => Style::default(),
+                    };
+                    ListItem::new(entry.summary()).style(style)
+                })
+                .collect();
+            let list = List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(format!(
+                    "History, {} of {} sends [Esc]",
+                    app.history.items.len(),
+                    app.history_entries.len()
+                )))
+                .highlight_style(
+                    Style::default()
+                        .bg(Color::LightGreen)
+                        .add_modifier(Modifier::BOLD),
+                );
+            app.textarea.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Filter by machine"),
+            );
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, chunks[0], &mut app.history.state);
+            f.render_widget(app.textarea.widget(), chunks[1]);
+        }
+        ErrorPopByFail(_) => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Error [Enter/Esc]")
+                .style(Style::default().fg(Color::Red));
+            let text = app
+                .error
+                .as_ref()
+                .map(|err| err.to_string())
+                .unwrap_or_default();
+            let paragraph = Paragraph::new(text).wrap(Wrap { trim: true });
+            let area = centered_rect(60, 6, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        GroupSendPopByWakeGroup(_) => {
+            let entries = app.send_job.as_ref().map_or(&[][..], |job| &job.entries);
+            let failed = entries
+                .iter()
+                .any(|entry| matches!(entry.result, Some(Err(_))));
+            let style = if failed {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD)
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Wake {} [Enter/Esc]",
+                    app.selected_group().unwrap_or_default()
+                ))
+                .style(style);
+            let lines: Vec<Spans> = entries
+                .iter()
+                .map(|entry| {
+                    let status = match &entry.result {
+                        None => format!("{}/{} packets sent", entry.sent, entry.total),
+                        Some(Ok(())) => format!("sent {} packets", entry.total),
+                        Some(Err(err)) => format!("failed: {err}"),
+                    };
+                    Spans::from(format!("{:<20}{}", entry.machine.name, status))
+                })
+                .collect();
+            let height = (lines.len() as u16 + 2).min(f.size().height);
+            let paragraph = Paragraph::new(lines);
+            let area = centered_rect(60, height, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        DiscoveringByDiscover(_)
+        | DiscoveringByNext(_)
+        | DiscoveringByCancel(_)
+        | DiscoverNameByEdit(_) => {
+            if let Some(discovery) = &mut app.discovery {
+                let plan = discovery_plan(&app.machines, discovery);
+                let mut selected = 0;
+                let items: Vec<ListItem> = discovery
+                    .candidates
+                    .items
+                    .iter()
+                    .map(|candidate| {
+                        let mark = if candidate.selected { "[x]" } else { "[ ]" };
+                        let mut name = candidate.name.clone();
+                        if candidate.selected {
+                            if let Some(renamed) = plan
+                                .renamed
+                                .iter()
+                                .find(|renamed| renamed.index == selected)
+                            {
+                                name = format!("{} → {}", renamed.from, renamed.to);
+                            }
+                            selected += 1;
+                        }
+                        ListItem::new(Spans::from(format!(
+                            "{mark} {:<20}{:<20}{:<28}{}",
+                            name,
+                            candidate.neighbor.mac,
+                            candidate.neighbor.ip,
+                            candidate.neighbor.hostname.as_deref().unwrap_or("")
+                        )))
+                    })
+                    .collect();
+                let title = format!(
+                    "Discovered {}{} [Space: select, e: rename, Enter: import, Esc]",
+                    items.len(),
+                    if discovery.scanning {
+                        ", scanning…"
+                    } else {
+                        ""
+                    }
+                );
+                let height = (items.len() as u16 + 2).clamp(3, f.size().height);
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(title))
+                    .highlight_style(
+                        Style::default()
+                            .bg(Color::LightGreen)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                    .highlight_symbol(">> ");
+                let area = centered_rect(90, height, f.size());
+                f.render_widget(Clear, area);
+                f.render_stateful_widget(list, area, &mut discovery.candidates.state);
+            }
+            if let DiscoverNameByEdit(_) = &app.state_machine {
+                app.textarea
+                    .set_block(Block::default().borders(Borders::ALL).title("Machine Name"));
+                let widget = app.textarea.widget();
+                let area = centered_rect(60, 3, f.size());
+                f.render_widget(Clear, area);
+                f.render_widget(widget, area);
+            }
+        }
+        TransferMenuByTransfer(_) => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Import/Export [Esc]");
+            let text = "[i] Import machines from an ethers, CSV or JSON file\n[x] Export machines to an ethers, CSV or JSON file";
+            let paragraph = Paragraph::new(text);
+            let area = centered_rect(60, 4, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        ImportPathByImport(_) | ExportPathByExport(_) => {
+            let title = if let ImportPathByImport(_) = &app.state_machine {
+                "Import from file (.csv, .json, otherwise ethers)"
+            } else {
+                "Export to file (.csv, .json, otherwise ethers)"
+            };
+            app.textarea
+                .set_block(Block::default().borders(Borders::ALL).title(title));
+            let widget = app.textarea.widget();
+            let area = centered_rect(60, 3, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(widget, area);
+        }
+        ImportPreviewByImport(_) => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Import Preview");
+            let plan = inventory::plan(&app.machines, app.importing.clone(), app.import_mode);
+            let mut lines = vec![
+                Spans::from(format!(
+                    "mode:   {} [m]",
+                    match app.import_mode {
+                        ImportMode::Merge => "merge",
+                        ImportMode::Replace => "replace",
+                    }
+                )),
+                Spans::from(format!(
+                    "add {}: {}",
+                    plan.added.len(),
+                    plan.added.join(", ")
+                )),
+                Spans::from(format!(
+                    "skip {} with a known MAC: {}",
+                    plan.duplicates.len(),
+                    plan.duplicates.join(", ")
+                )),
+            ];
+            if !plan.renamed.is_empty() {
+                lines.push(Spans::from(format!(
+                    "rename {} with a taken name: {}",
+                    plan.renamed.len(),
+                    plan.renamed
+                        .iter()
+                        .map(|renamed| format!("{} → {}", renamed.from, renamed.to))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+            if app.import_mode == ImportMode::Replace {
+                lines.push(Spans::from(format!(
+                    "remove {} configured machines",
+                    app.machines.len()
+                )));
+            }
+            lines.push(Spans::default());
+            lines.push(Spans::from("Apply import? (Y/n)"));
+            let paragraph = Paragraph::new(lines).wrap(Wrap { trim: true });
+            let area = centered_rect(60, 10, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph.block(block), area);
+        }
+        VerifyingByVerify(_) => {
+            let selected = app.selected_machine().unwrap_or_default();
+            let (status, style) = match &app.verification {
+                Some(Verification {
+                    outcome: Some(outcome @ VerifyOutcome::Up(_)),
+                    ..
+                }) => (
+                    outcome.to_string(),
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Some(Verification {
+                    outcome: Some(outcome),
+                    ..
+                }) => (outcome.to_string(), Style::default().fg(Color::Red)),
+                Some(verification) => (
+                    format!(
+                        "waiting… {}s / {}s",
+                        verification.started.elapsed().as_secs(),
+                        verification.timeout.as_secs()
+                    ),
+                    Style::default().fg(Color::Yellow),
+                ),
+                None => ("".into(), Style::default()),
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Verify [Enter/Esc]")
+                .style(style);
             let text = format!(
-                "name: {}\nMAC:  {}\n\nSent wol packet!",
-                app.machines.items[selected].0, app.machines.items[selected].1
+                "name: {}\nMAC:  {}\n\n{}",
+                app.machines[selected].name, app.machines[selected].mac_address, status
             );
             let paragraph = Paragraph::new(text);
             let area = centered_rect(60, 6, f.size());
@@ -421,6 +1935,156 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     };
 }
 
+fn status_span<'a>(poller: Option<&StatusPoller>, machine: &config::Machine) -> Span<'a> {
+    let status = if machine.probe.is_some() {
+        poller.and_then(|poller| poller.status(&machine.name))
+    } else {
+        None
+    };
+    match status {
+        Some(status) => {
+            let last_seen = status
+                .last_seen
+                .map_or_else(|| "never".into(), |seen| status::format_ago(seen.elapsed()));
+            let (label, color) = if status.online {
+                ("online", Color::Green)
+            } else {
+                ("offline", Color::Red)
+            };
+            Span::styled(
+                format!("● {:<8}{:<10}", label, last_seen),
+                Style::default().fg(color),
+            )
+        }
+        None => Span::styled(
+            format!("○ {:<8}{:<10}", "unknown", ""),
+            Style::default().fg(Color::DarkGray),
+        ),
+    }
+}
+
+/// Pads `text` to `width` and highlights the characters matched by the fuzzy `filter`.
+fn highlighted<'a>(text: &str, filter: &str, width: usize) -> Vec<Span<'a>> {
+    let matched = if filter.is_empty() {
+        vec![]
+    } else {
+        fuzzy::fuzzy_match(filter, text).unwrap_or_default()
+    };
+    let mut spans: Vec<Span> = text
+        .chars()
+        .enumerate()
+        .map(|(position, c)| {
+            if matched.contains(&position) {
+                Span::styled(
+                    c.to_string(),
+                    Style::default()
+                        .fg(Color::Blue)
+                        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                )
+            } else {
+                Span::raw(c.to_string())
+            }
+        })
+        .collect();
+    let len = text.chars().count();
+    if len < width {
+        spans.push(Span::raw(" ".repeat(width - len)));
+    }
+    spans
+}
+
+fn diff_line<'a>(label: &'a str, old: &str, new: &str) -> Spans<'a> {
+    if old == new {
+        Spans::from(format!("{label} {old}"))
+    } else {
+        Spans::from(vec![
+            Span::raw(format!("{label} ")),
+            Span::styled(
+                old.to_string(),
+                Style::default()
+                    .fg(Color::Red)
+                    .add_modifier(Modifier::CROSSED_OUT),
+            ),
+            Span::raw(" → "),
+            Span::styled(
+                new.to_string(),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ])
+    }
+}
+
+/// Merges the selected discovery candidates into `machines`. Renamed indices count the
+/// selected candidates only.
+fn discovery_plan(machines: &[config::Machine], discovery: &Discovery) -> inventory::ImportPlan {
+    let imported = discovery
+        .candidates
+        .items
+        .iter()
+        .filter(|candidate| candidate.selected)
+        .map(|candidate| config::Machine {
+            name: match candidate.name.trim() {
+                "" => candidate.neighbor.ip.to_string(),
+                name => name.to_string(),
+            },
+            mac_address: candidate.neighbor.mac,
+            ..Default::default()
+        })
+        .collect();
+    inventory::plan(machines, imported, ImportMode::Merge)
+}
+
+fn prefilled<'a>(value: &str) -> TextArea<'a> {
+    let mut textarea = TextArea::new(vec![value.to_string()]);
+    textarea.move_cursor(CursorMove::End);
+    textarea
+}
+
+fn validation_style(valid: bool) -> Style {
+    if valid {
+        Style::default()
+            .fg(Color::Green)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Red)
+    }
+}
+
+fn is_valid_target(target: &str) -> bool {
+    target.is_empty() || target.parse::<Target>().is_ok()
+}
+
+fn is_valid_secureon(password: &str) -> bool {
+    password.is_empty() || password.parse::<SecureOn>().is_ok()
+}
+
+fn is_valid_port(port: &str) -> bool {
+    port.is_empty() || port.parse::<u16>().is_ok_and(|port| port != 0)
+}
+
+fn duplicate_style(duplicate: Option<&str>) -> Style {
+    match duplicate {
+        Some(_) => Style::default().fg(Color::Yellow),
+        None => Style::default(),
+    }
+}
+
+fn duplicate_warning(duplicate: Option<&str>) -> String {
+    match duplicate {
+        Some(name) => format!("warning: {name} has the same MAC address"),
+        None => "".into(),
+    }
+}
+
+fn mac_label(oui: &OuiTable, mac: &MacAddress) -> String {
+    match oui.describe(mac) {
+        Some(vendor) => format!("{mac} ({vendor})"),
+        None => mac.to_string(),
+    }
+}
+
 fn centered_rect(percent_x: u16, y_line: u16, r: Rect) -> Rect {
     let vertical_padding = (r.height - 3) / 2;
 
@@ -0,0 +1,143 @@
+use crate::app::config::{MacAddress, Machine};
+
+/// An edit to the machine list, kept until it is saved so it can be applied again on
+/// top of a config file that another program changed in the meantime.
+#[derive(Debug, Clone)]
+pub enum Change {
+    Add(Vec<Machine>),
+    /// Replaces the machine at `index`, or wherever `key` moved to since.
+    Update {
+        index: usize,
+        key: MachineKey,
+        machine: Machine,
+    },
+    /// Removes the machine at `index`, or wherever `key` moved to since.
+    Delete {
+        index: usize,
+        key: MachineKey,
+    },
+    Replace(Vec<Machine>),
+}
+
+/// Identifies the machine a change was made to. Names aren't enough on their own, since
+/// hand edited configs can repeat them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineKey {
+    name: String,
+    mac_address: MacAddress,
+}
+
+impl MachineKey {
+    pub fn of(machine: &Machine) -> MachineKey {
+        MachineKey {
+            name: machine.name.clone(),
+            mac_address: machine.mac_address,
+        }
+    }
+
+    fn matches(&self, machine: &Machine) -> bool {
+        machine.name == self.name && machine.mac_address == self.mac_address
+    }
+
+    /// The machine at `index` if it is still this one, otherwise the first match.
+    fn position(&self, machines: &[Machine], index: usize) -> Option<usize> {
+        match machines.get(index) {
+            Some(machine) if self.matches(machine) => Some(index),
+            _ => machines.iter().position(|machine| self.matches(machine)),
+        }
+    }
+}
+
+impl Change {
+    pub fn update(machines: &[Machine], index: usize, machine: Machine) -> Change {
+        Change::Update {
+            index,
+            key: MachineKey::of(&machines[index]),
+            machine,
+        }
+    }
+
+    pub fn delete(machines: &[Machine], index: usize) -> Change {
+        Change::Delete {
+            index,
+            key: MachineKey::of(&machines[index]),
+        }
+    }
+
+    pub fn apply(&self, machines: &mut Vec<Machine>) {
+        match self {
+            Change::Add(added) => machines.extend(added.iter().cloned()),
+            Change::Update {
+                index,
+                key,
+                machine,
+            } => match key.position(machines, *index) {
+                Some(position) => machines[position] = machine.clone(),
+                // Deleted elsewhere, keep the edited version.
+                None => machines.push(machine.clone()),
+            },
+            Change::Delete { index, key } => {
+                if let Some(position) = key.position(machines, *index) {
+                    machines.remove(position);
+                }
+            }
+            Change::Replace(replaced) => *machines = replaced.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::test_machine as machine;
+
+    fn names(machines: &[Machine]) -> Vec<String> {
+        machines
+            .iter()
+            .map(|machine| format!("{}/{}", machine.name, machine.mac_address.0[5]))
+            .collect()
+    }
+
+    #[test]
+    fn delete_removes_only_the_chosen_duplicate() {
+        let mut machines = vec![machine("nas", 1), machine("nas", 2), machine("nas", 2)];
+        Change::delete(&machines, 2).apply(&mut machines);
+        assert_eq!(names(&machines), ["nas/1", "nas/2"]);
+    }
+
+    #[test]
+    fn update_rewrites_the_edited_duplicate() {
+        let mut machines = vec![machine("nas", 1), machine("nas", 2)];
+        Change::update(&machines, 1, machine("backup", 2)).apply(&mut machines);
+        assert_eq!(names(&machines), ["nas/1", "backup/2"]);
+    }
+
+    #[test]
+    fn changes_follow_a_machine_that_moved() {
+        let mut machines = vec![machine("nas", 1), machine("desktop", 2)];
+        let update = Change::update(&machines, 1, machine("workstation", 2));
+        let delete = Change::delete(&machines, 0);
+
+        // Another program inserted a machine in front of both.
+        let mut reloaded = vec![
+            machine("printer", 3),
+            machine("nas", 1),
+            machine("desktop", 2),
+        ];
+        update.apply(&mut reloaded);
+        delete.apply(&mut reloaded);
+        assert_eq!(names(&reloaded), ["printer/3", "workstation/2"]);
+
+        update.apply(&mut machines);
+        assert_eq!(names(&machines), ["nas/1", "workstation/2"]);
+    }
+
+    #[test]
+    fn update_of_a_machine_deleted_elsewhere_adds_it_back() {
+        let machines = vec![machine("nas", 1)];
+        let update = Change::update(&machines, 0, machine("nas", 9));
+        let mut reloaded = vec![];
+        update.apply(&mut reloaded);
+        assert_eq!(names(&reloaded), ["nas/9"]);
+    }
+}
@@ -1,46 +1,757 @@
-use serde::{Deserialize, Serialize};
+use fs2::FileExt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
-    fs::{self, OpenOptions},
-    io,
-    path::Path,
+    collections::hash_map::DefaultHasher,
+    env, fmt,
+    fs::{self, File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
 };
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+use crate::error::{Error, Result};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Machine {
     pub name: String,
-    pub mac_address: String,
+    pub mac_address: MacAddress,
+    /// Broadcast, unicast or IPv6 multicast address the magic packet is sent to.
+    #[serde(default)]
+    pub target: Option<Target>,
+    /// UDP port, usually 7 or 9.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Local address or interface name to send from.
+    #[serde(default)]
+    pub bind: Option<String>,
+    /// `host:port` of a `woltui relay` in the machine's subnet, for routers that don't
+    /// forward broadcasts. The packet goes there instead of to `target`.
+    #[serde(default)]
+    pub via: Option<String>,
+    /// Packets per wake, overriding the global `repeat`.
+    #[serde(default)]
+    pub repeat: Option<u32>,
+    /// Milliseconds between packets, overriding the global `interval_ms`.
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    /// `udp` broadcast, or a raw `ethernet` frame out of the `bind` interface.
+    #[serde(default)]
+    pub transport: Transport,
+    /// SecureOn password appended to the magic packet.
+    #[serde(default)]
+    pub secureon: Option<SecureOn>,
+    /// Groups the machine belongs to, e.g. a rack.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// How to check that the machine came up after a wake.
+    #[serde(default)]
+    pub probe: Option<Probe>,
+}
+
+/// A MAC address, read as `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff`, Cisco style
+/// `aabb.ccdd.eeff` or bare `aabbccddeeff` in any case, and written as lowercase
+/// `aa:bb:cc:dd:ee:ff`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl MacAddress {
+    /// Broadcast, multicast and all-zero addresses can't belong to a single machine.
+    pub fn is_unicast(&self) -> bool {
+        self.0[0] & 0x01 == 0 && self.0 != [0; 6]
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let groups: Vec<&str> = if s.contains(':') && s.contains('-') {
+            return Err(format!("invalid MAC address: {s}"));
+        } else if s.contains([':', '-']) {
+            s.split([':', '-']).collect()
+        } else if s.contains('.') {
+            s.split('.').collect()
+        } else {
+            vec![s]
+        };
+        // Colon and dash notations allow leading zeros to be left out, as in `0:1b:...`.
+        let width = 12 / groups.len();
+        let valid = matches!(groups.len(), 1 | 3 | 6)
+            && groups.iter().all(|group| {
+                (group.len() == width || (width == 2 && group.len() == 1))
+                    && group.chars().all(|c| c.is_ascii_hexdigit())
+            });
+        if !valid {
+            return Err(format!("invalid MAC address: {s}"));
+        }
+        let hex: String = groups
+            .iter()
+            .map(|group| format!("{group:0>width$}"))
+            .collect();
+        let mut octets = [0; 6];
+        for (i, octet) in octets.iter_mut().enumerate() {
+            *octet = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| format!("invalid MAC address: {s}"))?;
+        }
+        Ok(MacAddress(octets))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A SecureOn password, 4 or 6 hex bytes separated by `:` or `-` like `aa:bb:cc:dd`, and
+/// written as lowercase `aa:bb:cc:dd`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecureOn(Vec<u8>);
+
+impl SecureOn {
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl FromStr for SecureOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let bytes: Option<Vec<u8>> = s
+            .split([':', '-'])
+            .map(|byte| match byte.len() {
+                // `from_str_radix` would also take a sign, as in `+1`.
+                2 if byte.bytes().all(|digit| digit.is_ascii_hexdigit()) => {
+                    u8::from_str_radix(byte, 16).ok()
+                }
+                _ => None,
+            })
+            .collect();
+        match bytes {
+            Some(bytes) if bytes.len() == 4 || bytes.len() == 6 => Ok(SecureOn(bytes)),
+            _ => Err(format!(
+                "invalid SecureOn password: {s}, expected 4 or 6 hex bytes"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SecureOn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self.0.iter().map(|byte| format!("{byte:02x}")).collect();
+        write!(f, "{}", bytes.join(":"))
+    }
+}
+
+impl Serialize for SecureOn {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecureOn {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// An IPv4 or IPv6 address with an optional IPv6 scope, written like `192.168.1.255`,
+/// `ff02::1%eth0` or `[fe80::1%2]`. The scope is an interface name or index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub ip: IpAddr,
+    pub scope: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .unwrap_or(s);
+        let (ip, scope) = match s.split_once('%') {
+            Some((ip, scope)) => (ip, Some(scope)),
+            None => (s, None),
+        };
+        let ip: IpAddr = ip
+            .parse()
+            .map_err(|_| format!("invalid IP address: {ip}"))?;
+        match scope {
+            Some(_) if ip.is_ipv4() => Err(format!("IPv4 address {ip} cannot have a scope")),
+            Some("") => Err("empty IPv6 scope".into()),
+            scope => Ok(Target {
+                ip,
+                scope: scope.map(|scope| scope.to_string()),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.scope {
+            Some(scope) => write!(f, "{}%{}", self.ip, scope),
+            None => write!(f, "{}", self.ip),
+        }
+    }
+}
+
+impl Serialize for Target {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Target {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// UDP datagram to `target` and `port`.
+    #[default]
+    Udp,
+    /// Layer-2 frame with EtherType 0x0842, Linux only and needs CAP_NET_RAW.
+    Ethernet,
+}
+
+/// Group holding machines without any tag.
+pub const UNTAGGED: &str = "untagged";
+
+impl Machine {
+    pub fn in_group(&self, group: &str) -> bool {
+        self.tags.iter().any(|tag| tag == group) || (group == UNTAGGED && self.tags.is_empty())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeMethod {
+    /// Connect to a TCP port; a refused connection also counts as up.
+    #[default]
+    Tcp,
+    /// ICMP echo over an unprivileged ping socket.
+    Icmp,
+    /// Check that the host answered ARP or IPv6 neighbor discovery recently, read from
+    /// the kernel neighbor table with `ip`.
+    Arp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Probe {
+    pub host: String,
+    #[serde(default)]
+    pub method: ProbeMethod,
+    /// TCP port for the `tcp` method, defaults to 22.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Seconds to wait for the machine after a wake, defaults to 120.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Config {
+    /// Seconds between online status checks of machines with a `probe`, defaults to 30.
+    #[serde(default)]
+    pub status_interval_secs: Option<u64>,
+    /// Packets sent per wake, for networks that drop some of them, defaults to 1.
+    #[serde(default)]
+    pub repeat: Option<u32>,
+    /// Milliseconds between repeated packets, defaults to 100.
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    /// IEEE `oui.txt` or Wireshark `manuf` file for vendor names, defaults to the
+    /// system copy.
+    #[serde(default)]
+    pub oui_file: Option<PathBuf>,
+    /// Bearer token required by `woltui serve`, which doesn't start without one.
+    #[serde(default)]
+    pub api_token: Option<String>,
+    /// Broker for `woltui mqtt`.
+    #[serde(default)]
+    pub mqtt: Option<Mqtt>,
+    #[serde(default)]
     pub machines: Vec<Machine>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<Schedule>,
 }
 
-pub fn read_config(file_path: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+/// A recurring wake of a machine or group, given as a cron expression or as days and
+/// a time, e.g. `days = ["weekdays"]` and `time = "07:30"`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Schedule {
+    /// Cron expression. Five fields are read like crontab, where 0 and 7 are Sunday. Six
+    /// or seven, starting with seconds, are read by the `cron` crate, where 1 is Sunday.
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// Day names like `mon`, or `weekdays` and `weekends`. Every day when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<String>,
+    /// Time of day as `HH:MM`.
+    #[serde(default)]
+    pub time: Option<String>,
+    /// IANA timezone like `Europe/Berlin`, defaults to the local timezone.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub machine: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// MQTT broker settings, given as an `[mqtt]` table.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Mqtt {
+    pub host: String,
+    /// Defaults to 1883.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// First level of every topic, defaults to `woltui`.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Defaults to `woltui-<pid>`.
+    #[serde(default)]
+    pub client_id: Option<String>,
+}
+
+/// Environment variable overriding the config location.
+pub const CONFIG_ENV: &str = "WOLTUI_CONFIG";
+
+/// Resolves the config file location. In order of precedence: the `--config` flag,
+/// `$WOLTUI_CONFIG`, `$XDG_CONFIG_HOME/woltui/config.toml` and the legacy `~/.wol/config`.
+/// A legacy config is copied to the XDG location the first time it is resolved.
+pub fn resolve_config_path(flag: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = flag {
+        return Ok(path.to_path_buf());
+    }
+    if let Some(path) = env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let home_dir = dirs::home_dir();
+    let legacy = home_dir
+        .as_ref()
+        .map(|home_dir| home_dir.join(".wol").join("config"));
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir.as_ref().map(|home_dir| home_dir.join(".config")))
+        .map(|config_dir| config_dir.join("woltui").join("config.toml"));
+
+    match (xdg, legacy) {
+        (Some(xdg), Some(legacy)) if !xdg.exists() && legacy.exists() => {
+            match migrate_legacy_config(&legacy, &xdg) {
+                Ok(()) => Ok(xdg),
+                Err(_) => Ok(legacy),
+            }
+        }
+        (Some(xdg), _) => Ok(xdg),
+        (None, Some(legacy)) => Ok(legacy),
+        (None, None) => Err(Error::Config {
+            path: PathBuf::from("~/.config/woltui/config.toml"),
+            source: io::Error::new(io::ErrorKind::NotFound, "Home directory not found"),
+        }),
+    }
+}
+
+fn migrate_legacy_config(legacy: &Path, xdg: &Path) -> io::Result<()> {
+    if let Some(parent_dir) = xdg.parent() {
+        fs::create_dir_all(parent_dir)?;
+    }
+    fs::copy(legacy, xdg)?;
+    Ok(())
+}
+
+/// Identifies the contents of the config file as last read or written, to notice when
+/// another program changed it. Hashes the contents since mtimes can be too coarse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    fn of(content: &str) -> Fingerprint {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Fingerprint(hasher.finish())
+    }
+}
+
+/// An advisory lock on the config, held while reading, checking and writing it.
+pub struct ConfigLock {
+    _file: File,
+}
+
+/// Waits for other instances to finish with the config. The lock is taken on a
+/// `<path>.lock` file because writes replace the config file itself.
+pub fn lock_config(file_path: &Path) -> Result<ConfigLock> {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(".lock");
+    let config_error = |source: io::Error| Error::Config {
+        path: file_path.to_path_buf(),
+        source,
+    };
+
+    if let Some(parent_dir) = file_path.parent() {
+        fs::create_dir_all(parent_dir).map_err(config_error)?;
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(PathBuf::from(path))
+        .map_err(config_error)?;
+    file.lock_exclusive().map_err(config_error)?;
+    Ok(ConfigLock { _file: file })
+}
+
+pub fn read_config(file_path: &Path) -> Result<Config> {
+    load_config(file_path).map(|(config, _)| config)
+}
+
+/// Reads the config along with the fingerprint to pass to `save_config`.
+pub fn load_config(file_path: &Path) -> Result<(Config, Fingerprint)> {
+    let config_error = |source: io::Error| Error::Config {
+        path: file_path.to_path_buf(),
+        source,
+    };
+
     if !file_path.exists() {
+        if let Some(parent_dir) = file_path.parent() {
+            fs::create_dir_all(parent_dir).map_err(config_error)?;
+        }
         let _ = OpenOptions::new()
             .write(true)
             .create_new(true)
             .open(file_path)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        return Ok(Config::default());
+            .map_err(config_error)?;
+        return Ok((Config::default(), Fingerprint::of("")));
     }
 
-    let content =
-        fs::read_to_string(file_path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    let config = toml::from_str(&content)?;
+    let content = fs::read_to_string(file_path).map_err(config_error)?;
+    let config = toml::from_str(&content).map_err(|err: toml::de::Error| {
+        let (line, column) = err
+            .span()
+            .map_or((1, 1), |span| line_column(&content, span.start));
+        Error::ConfigParse {
+            path: file_path.to_path_buf(),
+            line,
+            column,
+            message: err.message().to_string(),
+        }
+    })?;
 
-    Ok(config)
+    Ok((config, Fingerprint::of(&content)))
 }
 
-pub fn write_config(file_path: &Path, config: &Config) -> io::Result<()> {
+/// Writes the config unless it changed since it was read as `expected`, in which case
+/// it fails with `Error::Conflict`.
+pub fn save_config(
+    file_path: &Path,
+    config: &Config,
+    expected: &Fingerprint,
+) -> Result<Fingerprint> {
+    let _lock = lock_config(file_path)?;
+    let current = match fs::read_to_string(file_path) {
+        Ok(content) => Some(Fingerprint::of(&content)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(source) => {
+            return Err(Error::Config {
+                path: file_path.to_path_buf(),
+                source,
+            })
+        }
+    };
+    if current.as_ref() != Some(expected) {
+        return Err(Error::Conflict(file_path.to_path_buf()));
+    }
+    write_config(file_path, config)
+}
+
+/// Replaces the config atomically, keeping the previous version as a backup. Callers
+/// doing read-modify-write should hold `lock_config`.
+pub fn write_config(file_path: &Path, config: &Config) -> Result<Fingerprint> {
+    let config_error = |source: io::Error| Error::Config {
+        path: file_path.to_path_buf(),
+        source,
+    };
+
     if let Some(parent_dir) = file_path.parent() {
         if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir)?
+            fs::create_dir_all(parent_dir).map_err(config_error)?
+        }
+    }
+
+    let content = toml::to_string(config)
+        .map_err(|e| config_error(io::Error::new(io::ErrorKind::Other, e)))?;
+    if file_path.exists() {
+        fs::copy(file_path, backup_path(file_path)).map_err(config_error)?;
+    }
+    write_atomic(file_path, &content).map_err(config_error)?;
+
+    Ok(Fingerprint::of(&content))
+}
+
+/// Writes to a temporary file next to `file_path` and renames it over the original, so
+/// a crash leaves either the old or the new contents.
+fn write_atomic(file_path: &Path, content: &str) -> io::Result<()> {
+    // Write through symlinks, e.g. into a dotfiles repository.
+    let file_path = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+    let mut temp_path = file_path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", process::id()));
+    let temp_path = PathBuf::from(temp_path);
+
+    let result = (|| -> io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        if let Ok(metadata) = fs::metadata(&file_path) {
+            // Keep restrictive permissions, the config can hold SecureOn passwords.
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &file_path)?;
+        #[cfg(unix)]
+        if let Some(parent_dir) = file_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(parent_dir)?.sync_all()?;
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// The wake history kept next to the config, e.g. `~/.config/woltui/history.jsonl`.
+pub fn history_path(file_path: &Path) -> PathBuf {
+    file_path.with_file_name("history.jsonl")
+}
+
+/// The copy of the previous config kept by `write_config`, e.g. `config.toml.bak`.
+pub fn backup_path(file_path: &Path) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(".bak");
+    PathBuf::from(path)
+}
+
+/// Converts a byte offset into 1-based line and column numbers.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+/// Writes `contents` as the config in a fresh directory of its own, so tests using
+/// files next to the config, like the history, don't see each other's.
+#[cfg(test)]
+pub fn test_config_path(name: &str, contents: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("woltui-test-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// A machine named `name` whose MAC address ends in `last`, for tests.
+#[cfg(test)]
+pub fn test_machine(name: &str, last: u8) -> Machine {
+    Machine {
+        name: name.to_string(),
+        mac_address: MacAddress([0, 0x11, 0x22, 0x33, 0x44, last]),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_with_interface_scope_round_trips() {
+        let target: Target = "ff02::1%eth0".parse().unwrap();
+        assert_eq!(target.ip, "ff02::1".parse::<IpAddr>().unwrap());
+        assert_eq!(target.scope.as_deref(), Some("eth0"));
+        assert_eq!(target.to_string(), "ff02::1%eth0");
+        assert_eq!(target.to_string().parse::<Target>().unwrap(), target);
+    }
+
+    #[test]
+    fn bracketed_target_with_index_scope_round_trips() {
+        let target: Target = "[fe80::1%2]".parse().unwrap();
+        assert_eq!(target.ip, "fe80::1".parse::<IpAddr>().unwrap());
+        assert_eq!(target.scope.as_deref(), Some("2"));
+        assert_eq!(target.to_string(), "fe80::1%2");
+        assert_eq!(target.to_string().parse::<Target>().unwrap(), target);
+    }
+
+    #[test]
+    fn target_without_scope_round_trips() {
+        for value in ["192.168.1.255", "ff02::1"] {
+            let target: Target = value.parse().unwrap();
+            assert_eq!(target.scope, None);
+            assert_eq!(target.to_string(), value);
         }
     }
 
-    let content =
-        toml::to_string(config).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-    fs::write(file_path, content)
+    #[test]
+    fn target_rejects_ipv4_scope_and_empty_scope() {
+        assert!("1.2.3.4%eth0".parse::<Target>().is_err());
+        assert!("fe80::1%".parse::<Target>().is_err());
+        assert!("[fe80::1%]".parse::<Target>().is_err());
+        assert!("not-an-address".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn missing_config_is_created_and_loads_again() {
+        let path = test_config_path("missing", "");
+        fs::remove_file(&path).unwrap();
+
+        let (first, _) = load_config(&path).unwrap();
+        assert!(path.exists());
+        let (second, _) = load_config(&path).unwrap();
+        assert!(first.machines.is_empty());
+        assert!(second.machines.is_empty());
+    }
+
+    #[test]
+    fn mac_address_reads_every_notation() {
+        let expected = MacAddress([0xaa, 0xbb, 0xcc, 0x0d, 0xee, 0xff]);
+        for value in [
+            "aa:bb:cc:0d:ee:ff",
+            "AA-BB-CC-0D-EE-FF",
+            "aabb.cc0d.eeff",
+            "AABBCC0DEEFF",
+            "aa:bb:cc:d:ee:ff",
+            " aa-bb-cc-d-ee-ff ",
+        ] {
+            assert_eq!(value.parse::<MacAddress>(), Ok(expected), "{value}");
+        }
+    }
+
+    #[test]
+    fn mac_address_rejects_malformed_input() {
+        for value in [
+            "",
+            "aa:bb:cc:dd:ee",
+            "aa:bb:cc:dd:ee:ff:00",
+            "aabbccddeef",
+            "aabb.ccdd.eef",
+            "aa:bb-cc:dd-ee:ff",
+            "aa:bb:cc:dd:ee:fg",
+            "aaa:bb:cc:dd:ee:f",
+            "aabb.ccdd:eeff",
+            "zzzzzzzzzzzz",
+        ] {
+            assert!(value.parse::<MacAddress>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn mac_address_is_written_as_lowercase_colons() {
+        let mac: MacAddress = "00-1B-21-0A-BC-DE".parse().unwrap();
+        assert_eq!(mac.to_string(), "00:1b:21:0a:bc:de");
+        assert_eq!(mac.to_string().parse::<MacAddress>(), Ok(mac));
+    }
+
+    #[test]
+    fn config_with_older_mac_notation_loads_and_saves_normalized() {
+        let config: Config =
+            toml::from_str("[[machines]]\nname = \"nas\"\nmac_address = \"00:1B:21:0A:BC:DE\"\n")
+                .unwrap();
+        assert_eq!(
+            config.machines[0].mac_address,
+            MacAddress([0x00, 0x1b, 0x21, 0x0a, 0xbc, 0xde])
+        );
+
+        let written = toml::to_string(&config).unwrap();
+        assert!(written.contains("mac_address = \"00:1b:21:0a:bc:de\""));
+        let read: Config = toml::from_str(&written).unwrap();
+        assert_eq!(read.machines[0].mac_address, config.machines[0].mac_address);
+    }
+
+    #[test]
+    fn config_rejects_invalid_mac_address() {
+        let err =
+            toml::from_str::<Config>("[[machines]]\nname = \"nas\"\nmac_address = \"00:1B:21\"\n")
+                .unwrap_err();
+        assert!(err.message().contains("invalid MAC address"));
+    }
+
+    #[test]
+    fn secureon_reads_4_and_6_bytes() {
+        let password: SecureOn = "DE:AD:BE:EF".parse().unwrap();
+        assert_eq!(password.bytes(), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(password.to_string(), "de:ad:be:ef");
+
+        let password: SecureOn = "01-02-03-0a-0b-0c".parse().unwrap();
+        assert_eq!(password.bytes(), [0x01, 0x02, 0x03, 0x0a, 0x0b, 0x0c]);
+        assert_eq!(password.to_string(), "01:02:03:0a:0b:0c");
+    }
+
+    #[test]
+    fn secureon_rejects_wrong_lengths_and_malformed_bytes() {
+        for value in [
+            "01:02:03:04:05",
+            "01:02:03",
+            "",
+            "01:02:03:zz",
+            "1:02:03:04",
+            "01:02:03:04:",
+            "+1:02:03:04",
+            "01020304",
+        ] {
+            assert!(value.parse::<SecureOn>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn config_rejects_invalid_secureon_on_load() {
+        let path = test_config_path(
+            "secureon",
+            "[[machines]]\nname = \"nas\"\nmac_address = \"00:11:22:33:44:55\"\n\
+             secureon = \"de:ad:be\"\n",
+        );
+        match load_config(&path) {
+            Err(Error::ConfigParse { line, message, .. }) => {
+                assert_eq!(line, 4);
+                assert!(message.contains("invalid SecureOn password"), "{message}");
+            }
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
 }
@@ -0,0 +1,237 @@
+use std::{
+    collections::HashSet,
+    fs,
+    net::IpAddr,
+    process,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::app::{config::MacAddress, statefullist::StatefulList};
+
+const ARP_TABLE: &str = "/proc/net/arp";
+
+/// A host the kernel has resolved a link-layer address for.
+#[derive(Debug, Clone)]
+pub struct Neighbor {
+    pub ip: IpAddr,
+    pub mac: MacAddress,
+    pub hostname: Option<String>,
+}
+
+/// A discovered neighbor that can be imported as a machine.
+pub struct Candidate {
+    pub neighbor: Neighbor,
+    pub name: String,
+    pub selected: bool,
+}
+
+enum DiscoveryEvent {
+    Found(Vec<Neighbor>),
+    Resolved(usize, String),
+    Done,
+}
+
+/// Reads the neighbor tables and resolves their names on a background thread.
+pub struct Discovery {
+    pub candidates: StatefulList<Candidate>,
+    pub scanning: bool,
+    receiver: Receiver<DiscoveryEvent>,
+}
+
+impl Discovery {
+    /// Starts a scan, leaving out neighbors whose MAC is in `known`.
+    pub fn spawn(known: &[MacAddress]) -> Discovery {
+        let known: HashSet<MacAddress> = known.iter().copied().collect();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let neighbors: Vec<Neighbor> = scan()
+                .into_iter()
+                .filter(|neighbor| !known.contains(&neighbor.mac))
+                .collect();
+            let addresses: Vec<IpAddr> = neighbors.iter().map(|neighbor| neighbor.ip).collect();
+            let _ = sender.send(DiscoveryEvent::Found(neighbors));
+            for (index, ip) in addresses.into_iter().enumerate() {
+                if let Ok(hostname) = dns_lookup::lookup_addr(&ip) {
+                    if hostname.parse::<IpAddr>().is_err() {
+                        let _ = sender.send(DiscoveryEvent::Resolved(index, hostname));
+                    }
+                }
+            }
+            let _ = sender.send(DiscoveryEvent::Done);
+        });
+
+        Discovery {
+            candidates: StatefulList::with_items(vec![]),
+            scanning: true,
+            receiver,
+        }
+    }
+
+    /// Applies the results reported by the scan thread.
+    pub fn poll(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                DiscoveryEvent::Found(neighbors) => {
+                    self.candidates = StatefulList::with_items(
+                        neighbors
+                            .into_iter()
+                            .map(|neighbor| Candidate {
+                                name: neighbor.ip.to_string(),
+                                neighbor,
+                                selected: false,
+                            })
+                            .collect(),
+                    );
+                    if !self.candidates.items.is_empty() {
+                        self.candidates.state.select(Some(0));
+                    }
+                }
+                DiscoveryEvent::Resolved(index, hostname) => {
+                    if let Some(candidate) = self.candidates.items.get_mut(index) {
+                        // Keep names the user already typed.
+                        if candidate.name == candidate.neighbor.ip.to_string() {
+                            candidate.name = short_name(&hostname).to_string();
+                        }
+                        candidate.neighbor.hostname = Some(hostname);
+                    }
+                }
+                DiscoveryEvent::Done => self.scanning = false,
+            }
+        }
+    }
+
+    pub fn selected_candidate(&mut self) -> Option<&mut Candidate> {
+        let selected = self.candidates.state.selected()?;
+        self.candidates.items.get_mut(selected)
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let Some(candidate) = self.selected_candidate() {
+            candidate.selected = !candidate.selected;
+        }
+    }
+}
+
+/// Neighbors from `/proc/net/arp` and `ip neigh`, one per MAC and address.
+pub fn scan() -> Vec<Neighbor> {
+    let mut neighbors = fs::read_to_string(ARP_TABLE)
+        .map(|table| parse_proc_arp(&table))
+        .unwrap_or_default();
+    // `ip` reads the netlink neighbor table, which also has IPv6 entries.
+    if let Ok(output) = process::Command::new("ip")
+        .arg("neigh")
+        .arg("show")
+        .output()
+    {
+        if output.status.success() {
+            neighbors.extend(parse_ip_neigh(&String::from_utf8_lossy(&output.stdout)));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    neighbors.retain(|neighbor| seen.insert((neighbor.mac, neighbor.ip)));
+    neighbors
+}
+
+/// Parses the complete entries of `/proc/net/arp` formatted text.
+pub fn parse_proc_arp(table: &str) -> Vec<Neighbor> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            if columns.len() < 4 || columns[2] == "0x0" {
+                return None;
+            }
+            neighbor(columns[0], columns[3])
+        })
+        .collect()
+}
+
+/// Parses `ip neigh show` output, skipping entries without a link-layer address.
+pub fn parse_ip_neigh(output: &str) -> Vec<Neighbor> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let lladdr = columns.iter().position(|column| *column == "lladdr")?;
+            neighbor(columns.first()?, columns.get(lladdr + 1)?)
+        })
+        .collect()
+}
+
+fn neighbor(ip: &str, mac: &str) -> Option<Neighbor> {
+    let mac: MacAddress = mac.parse().ok()?;
+    if mac == MacAddress::default() {
+        return None;
+    }
+    Some(Neighbor {
+        ip: ip.parse().ok()?,
+        mac,
+        hostname: None,
+    })
+}
+
+/// The host part of a fully qualified name.
+fn short_name(hostname: &str) -> &str {
+    hostname.split('.').next().unwrap_or(hostname)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_NET_ARP: &str = include_str!("../../tests/fixtures/proc_net_arp");
+    const IP_NEIGH_SHOW: &str = include_str!("../../tests/fixtures/ip_neigh_show");
+
+    fn pairs(neighbors: &[Neighbor]) -> Vec<(String, String)> {
+        neighbors
+            .iter()
+            .map(|neighbor| (neighbor.ip.to_string(), neighbor.mac.to_string()))
+            .collect()
+    }
+
+    fn pair(ip: &str, mac: &str) -> (String, String) {
+        (ip.to_string(), mac.to_string())
+    }
+
+    #[test]
+    fn proc_arp_keeps_complete_entries_only() {
+        // 0x0 flags are incomplete entries, whatever their address column says, and the
+        // all-zero address is never a real neighbor.
+        assert_eq!(
+            pairs(&parse_proc_arp(PROC_NET_ARP)),
+            vec![
+                pair("192.168.1.1", "a0:b1:c2:d3:e4:f5"),
+                pair("192.168.1.40", "10:20:30:40:50:60"),
+            ]
+        );
+    }
+
+    #[test]
+    fn proc_arp_without_entries_is_empty() {
+        let header = PROC_NET_ARP.lines().next().unwrap();
+        assert!(parse_proc_arp(header).is_empty());
+        assert!(parse_proc_arp("").is_empty());
+    }
+
+    #[test]
+    fn ip_neigh_skips_rows_without_lladdr_and_keeps_ipv6() {
+        assert_eq!(
+            pairs(&parse_ip_neigh(IP_NEIGH_SHOW)),
+            vec![
+                pair("192.168.1.1", "a0:b1:c2:d3:e4:f5"),
+                pair("192.168.1.70", "10:20:30:40:50:61"),
+                pair("fe80::1", "a0:b1:c2:d3:e4:f5"),
+                pair("2001:db8::42", "52:54:00:12:34:56"),
+            ]
+        );
+    }
+
+    #[test]
+    fn short_name_strips_the_domain() {
+        assert_eq!(short_name("nas.lan.example"), "nas");
+        assert_eq!(short_name("nas"), "nas");
+    }
+}
@@ -0,0 +1,35 @@
+/// Matches `needle` as a case-insensitive subsequence of `haystack`, returning the char
+/// positions in `haystack` that matched.
+pub fn fuzzy_match(needle: &str, haystack: &str) -> Option<Vec<usize>> {
+    let mut positions = vec![];
+    let mut needle_chars = needle.chars().flat_map(char::to_lowercase).peekable();
+    for (position, c) in haystack.chars().enumerate() {
+        let Some(&wanted) = needle_chars.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(wanted)) {
+            positions.push(position);
+            needle_chars.next();
+        }
+    }
+    needle_chars.peek().is_none().then_some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_matched_positions_ignoring_case() {
+        assert_eq!(fuzzy_match("nS", "Nas-server"), Some(vec![0, 2]));
+        assert_eq!(fuzzy_match("srv", "nas-server"), Some(vec![4, 6, 7]));
+        assert_eq!(fuzzy_match("", "nas"), Some(vec![]));
+    }
+
+    #[test]
+    fn needs_every_char_in_order() {
+        assert_eq!(fuzzy_match("san", "nas"), None);
+        assert_eq!(fuzzy_match("nass", "nas"), None);
+        assert_eq!(fuzzy_match("x", ""), None);
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    app::{
+        config::{MacAddress, Machine, Transport},
+        wake,
+    },
+    error::{Error, Result},
+};
+
+/// One send attempt, stored as a line of JSON in the history file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub timestamp: DateTime<Utc>,
+    /// The local user who sent the packet.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// What sent the packet, e.g. `tui` or `cli`.
+    pub source: String,
+    pub machine: String,
+    pub mac: MacAddress,
+    pub target: String,
+    pub transport: Transport,
+    /// `None` when the packet was sent, otherwise why it wasn't.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Outcome of the wake verification, for machines with a `probe`.
+    #[serde(default)]
+    pub verification: Option<String>,
+}
+
+impl Entry {
+    pub fn new(machine: &Machine, source: &str, result: &Result<()>) -> Entry {
+        Entry {
+            timestamp: Utc::now(),
+            user: env::var("USER").ok().filter(|user| !user.is_empty()),
+            source: source.to_string(),
+            machine: machine.name.clone(),
+            mac: machine.mac_address,
+            target: wake::target_label(machine),
+            transport: machine.transport,
+            error: result.as_ref().err().map(|err| err.to_string()),
+            verification: None,
+        }
+    }
+
+    /// A single line summary in local time.
+    pub fn summary(&self) -> String {
+        let result = match &self.error {
+            None => "sent",
+            Some(_) => "failed",
+        };
+        let mut line = format!(
+            "{}  {:<20}{}  {:<8}{:<7}via {}",
+            self.timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            self.machine,
+            self.mac,
+            match self.transport {
+                Transport::Udp => "udp",
+                Transport::Ethernet => "ethernet",
+            },
+            result,
+            self.target,
+        );
+        if let Some(err) = &self.error {
+            line.push_str(&format!(": {err}"));
+        }
+        if let Some(verification) = &self.verification {
+            line.push_str(&format!(", {verification}"));
+        }
+        line.push_str(&format!(
+            " ({}{})",
+            self.user
+                .as_deref()
+                .map_or_else(String::new, |user| format!("{user}, ")),
+            self.source
+        ));
+        line
+    }
+}
+
+pub fn append(path: &Path, entry: &Entry) -> Result<()> {
+    let file_error = |source: io::Error| Error::File {
+        path: path.to_path_buf(),
+        source,
+    };
+    let line = serde_json::to_string(entry).map_err(|err| Error::Parse(err.to_string()))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(file_error)?;
+    // One write per line keeps concurrent appends from interleaving.
+    file.write_all(format!("{line}\n").as_bytes())
+        .map_err(file_error)
+}
+
+/// Reads the history oldest first, skipping lines that can't be parsed.
+pub fn read(path: &Path) -> Result<Vec<Entry>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(source) => {
+            return Err(Error::File {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
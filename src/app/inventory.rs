@@ -0,0 +1,327 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::{
+    app::config::{Config, MacAddress, Machine, Probe},
+    error::{Error, Result},
+};
+
+/// File formats machines can be imported from and exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// `/etc/ethers`: a MAC and a hostname per line
+    Ethers,
+    /// `name,mac,host,broadcast,port,interface,groups` with a header row
+    Csv,
+    /// The config layout as JSON
+    Json,
+}
+
+impl Format {
+    /// Guesses the format from the file extension, falling back to ethers.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Format::Csv,
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Ethers,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ImportMode {
+    /// Keep configured machines and add the new ones
+    #[default]
+    Merge,
+    /// Replace all configured machines with the imported ones
+    Replace,
+}
+
+/// One row of the CSV format.
+#[derive(Serialize, Deserialize, Default)]
+struct CsvRecord {
+    name: String,
+    mac: MacAddress,
+    /// Host to probe after a wake.
+    #[serde(default)]
+    host: Option<String>,
+    /// Target address of the magic packet.
+    #[serde(default)]
+    broadcast: Option<String>,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    interface: Option<String>,
+    /// Groups separated by `;`.
+    #[serde(default)]
+    groups: Option<String>,
+}
+
+/// The outcome of importing into the configured machines.
+pub struct ImportPlan {
+    /// The machine list after the import.
+    pub machines: Vec<Machine>,
+    pub added: Vec<String>,
+    /// Imported machines left out because their MAC is already present.
+    pub duplicates: Vec<String>,
+    /// Added machines whose name was already taken.
+    pub renamed: Vec<Renamed>,
+}
+
+pub struct Renamed {
+    /// Position in the imported machines.
+    pub index: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// Reads machines from `path`, guessing the format from the extension when not given.
+pub fn read_file(path: &Path, format: Option<Format>) -> Result<Vec<Machine>> {
+    let contents = fs::read_to_string(path).map_err(|source| Error::File {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse(format.unwrap_or_else(|| Format::from_path(path)), &contents)
+}
+
+pub fn write_file(path: &Path, format: Option<Format>, machines: &[Machine]) -> Result<()> {
+    let contents = render(format.unwrap_or_else(|| Format::from_path(path)), machines)?;
+    fs::write(path, contents).map_err(|source| Error::File {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+pub fn parse(format: Format, contents: &str) -> Result<Vec<Machine>> {
+    match format {
+        Format::Ethers => parse_ethers(contents),
+        Format::Csv => parse_csv(contents),
+        Format::Json => serde_json::from_str::<Config>(contents)
+            .map(|config| config.machines)
+            .map_err(|err| Error::Parse(format!("invalid JSON: {err}"))),
+    }
+}
+
+pub fn render(format: Format, machines: &[Machine]) -> Result<String> {
+    match format {
+        Format::Ethers => Ok(machines
+            .iter()
+            .map(|machine| format!("{} {}\n", machine.mac_address, machine.name))
+            .collect()),
+        Format::Csv => render_csv(machines),
+        Format::Json => {
+            let config = Config {
+                machines: machines.to_vec(),
+                ..Default::default()
+            };
+            serde_json::to_string_pretty(&config)
+                .map(|json| json + "\n")
+                .map_err(|err| Error::Parse(err.to_string()))
+        }
+    }
+}
+
+/// Combines `imported` with `existing`, dropping machines whose MAC is already taken
+/// and renaming those whose name is.
+pub fn plan(existing: &[Machine], imported: Vec<Machine>, mode: ImportMode) -> ImportPlan {
+    let mut machines = match mode {
+        ImportMode::Merge => existing.to_vec(),
+        ImportMode::Replace => vec![],
+    };
+    let mut macs: HashSet<MacAddress> =
+        machines.iter().map(|machine| machine.mac_address).collect();
+    let mut names: HashSet<String> = machines
+        .iter()
+        .map(|machine| machine.name.clone())
+        .collect();
+    let mut added = vec![];
+    let mut duplicates = vec![];
+    let mut renamed = vec![];
+    for (index, mut machine) in imported.into_iter().enumerate() {
+        if !macs.insert(machine.mac_address) {
+            duplicates.push(machine.name);
+            continue;
+        }
+        let name = unique_name(&names, &machine.name);
+        if name != machine.name {
+            renamed.push(Renamed {
+                index,
+                from: std::mem::replace(&mut machine.name, name.clone()),
+                to: name.clone(),
+            });
+        }
+        names.insert(name.clone());
+        added.push(name);
+        machines.push(machine);
+    }
+
+    ImportPlan {
+        machines,
+        added,
+        duplicates,
+        renamed,
+    }
+}
+
+/// `name`, or `name-2`, `name-3` and so on when it is taken.
+pub fn unique_name(taken: &HashSet<String>, name: &str) -> String {
+    if !taken.contains(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|suffix| format!("{name}-{suffix}"))
+        .find(|candidate| !taken.contains(candidate))
+        .expect("some suffix is free")
+}
+
+fn parse_ethers(contents: &str) -> Result<Vec<Machine>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(number, line)| {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut fields = line.split_whitespace();
+            let mac = fields.next()?;
+            Some(match (mac.parse::<MacAddress>(), fields.next()) {
+                (Ok(mac_address), Some(name)) => Ok(Machine {
+                    name: name.to_string(),
+                    mac_address,
+                    ..Default::default()
+                }),
+                (Err(err), _) => Err(Error::Parse(format!("ethers line {}: {err}", number + 1))),
+                (_, None) => Err(Error::Parse(format!(
+                    "ethers line {}: expected a MAC and a hostname",
+                    number + 1
+                ))),
+            })
+        })
+        .collect()
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<Machine>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+    reader
+        .deserialize::<CsvRecord>()
+        .map(|record| {
+            let record = record.map_err(|err| Error::Parse(format!("invalid CSV: {err}")))?;
+            let target = record
+                .broadcast
+                .filter(|target| !target.is_empty())
+                .map(|target| {
+                    target
+                        .parse()
+                        .map_err(|err| Error::Parse(format!("{}: {err}", record.name)))
+                })
+                .transpose()?;
+            Ok(Machine {
+                target,
+                port: record.port,
+                bind: record.interface.filter(|bind| !bind.is_empty()),
+                tags: record
+                    .groups
+                    .unwrap_or_default()
+                    .split(';')
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect(),
+                probe: record
+                    .host
+                    .filter(|host| !host.is_empty())
+                    .map(|host| Probe {
+                        host,
+                        ..Default::default()
+                    }),
+                name: record.name,
+                mac_address: record.mac,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+fn render_csv(machines: &[Machine]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for machine in machines {
+        writer
+            .serialize(CsvRecord {
+                name: machine.name.clone(),
+                mac: machine.mac_address,
+                host: machine.probe.as_ref().map(|probe| probe.host.clone()),
+                broadcast: machine.target.as_ref().map(|target| target.to_string()),
+                port: machine.port,
+                interface: machine.bind.clone(),
+                groups: Some(machine.tags.join(";")),
+            })
+            .map_err(|err| Error::Parse(err.to_string()))?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| Error::Parse(err.to_string()))?;
+    String::from_utf8(bytes).map_err(|err| Error::Parse(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::test_machine as machine;
+
+    #[test]
+    fn merge_renames_taken_names_and_skips_known_macs() {
+        let existing = vec![machine("nas", 1), machine("nas-2", 2)];
+        let imported = vec![
+            machine("nas", 3),
+            machine("desktop", 1),
+            machine("nas", 4),
+            machine("printer", 5),
+        ];
+        let plan = plan(&existing, imported, ImportMode::Merge);
+
+        let names: Vec<&str> = plan.machines.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["nas", "nas-2", "nas-3", "nas-4", "printer"]);
+        assert_eq!(plan.added, ["nas-3", "nas-4", "printer"]);
+        assert_eq!(plan.duplicates, ["desktop"]);
+        let renamed: Vec<(usize, &str, &str)> = plan
+            .renamed
+            .iter()
+            .map(|r| (r.index, r.from.as_str(), r.to.as_str()))
+            .collect();
+        assert_eq!(renamed, [(0, "nas", "nas-3"), (2, "nas", "nas-4")]);
+    }
+
+    #[test]
+    fn replace_only_renames_clashes_within_the_import() {
+        let existing = vec![machine("nas", 1)];
+        let imported = vec![machine("nas", 1), machine("nas", 2)];
+        let plan = plan(&existing, imported, ImportMode::Replace);
+
+        assert_eq!(plan.added, ["nas", "nas-2"]);
+        assert!(plan.duplicates.is_empty());
+        assert_eq!(plan.renamed.len(), 1);
+    }
+
+    #[test]
+    fn json_import_checks_secureon_passwords() {
+        let json = |password: &str| {
+            format!(
+                r#"{{"machines": [{{"name": "nas", "mac_address": "00:11:22:33:44:55", "secureon": "{password}"}}]}}"#
+            )
+        };
+        let machines = parse(Format::Json, &json("DE-AD-BE-EF")).unwrap();
+        assert_eq!(
+            machines[0]
+                .secureon
+                .as_ref()
+                .map(|password| password.to_string()),
+            Some("de:ad:be:ef".to_string())
+        );
+
+        let err = parse(Format::Json, &json("de:ad:be")).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid SecureOn password"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::app::config::MacAddress;
+
+/// Where distributions install the IEEE registry (`ieee-data`, `hwdata`) or Wireshark's
+/// `manuf` file, tried in order when no `oui_file` is configured.
+const DEFAULT_PATHS: &[&str] = &[
+    "/usr/share/ieee-data/oui.txt",
+    "/usr/share/hwdata/oui.txt",
+    "/usr/share/misc/oui.txt",
+    "/usr/share/wireshark/manuf",
+];
+
+/// Vendor names by the first three bytes of a MAC address.
+#[derive(Default)]
+pub struct OuiTable {
+    vendors: HashMap<[u8; 3], String>,
+}
+
+impl OuiTable {
+    /// Loads `path`, or the first system table found. Missing tables give an empty lookup.
+    pub fn load(path: Option<&Path>) -> OuiTable {
+        let candidates: Vec<PathBuf> = match path {
+            Some(path) => vec![path.to_path_buf()],
+            None => DEFAULT_PATHS.iter().map(PathBuf::from).collect(),
+        };
+        candidates
+            .iter()
+            .find_map(|path| fs::read(path).ok())
+            .map(|contents| OuiTable::parse(&String::from_utf8_lossy(&contents)))
+            .unwrap_or_default()
+    }
+
+    /// Parses the IEEE `oui.txt` format, `00-00-0C   (hex)\t\tCisco Systems, Inc`, and
+    /// Wireshark's `manuf` format, `00:00:0C\tCisco\tCisco Systems, Inc`.
+    pub fn parse(contents: &str) -> OuiTable {
+        let vendors = contents
+            .lines()
+            .filter_map(|line| {
+                let (prefix, vendor) = match line.split_once("(hex)") {
+                    Some((prefix, vendor)) => (prefix.trim(), vendor.trim()),
+                    None if !line.starts_with('#') => {
+                        let mut fields = line.split('\t');
+                        let prefix = fields.next()?.trim();
+                        let short = fields.next()?.trim();
+                        (prefix, fields.next().map_or(short, str::trim))
+                    }
+                    None => return None,
+                };
+                Some((parse_prefix(prefix)?, vendor.to_string()))
+            })
+            .filter(|(_, vendor)| !vendor.is_empty())
+            .collect();
+        OuiTable { vendors }
+    }
+
+    pub fn lookup(&self, mac: &MacAddress) -> Option<&str> {
+        let [a, b, c, ..] = mac.0;
+        self.vendors.get(&[a, b, c]).map(String::as_str)
+    }
+
+    /// The vendor of `mac` for display, noting addresses that have no registered vendor.
+    /// `None` when no table was loaded.
+    pub fn describe(&self, mac: &MacAddress) -> Option<&str> {
+        if self.vendors.is_empty() {
+            return None;
+        }
+        Some(match self.lookup(mac) {
+            Some(vendor) => vendor,
+            // Randomized and virtual machine addresses set the locally administered bit.
+            None if mac.0[0] & 0x02 != 0 => "locally administered",
+            None => "unknown vendor",
+        })
+    }
+}
+
+/// Parses a three byte prefix like `00-00-0C` or `00:00:0C`. Longer `manuf` prefixes
+/// with a mask, like `00:1B:C5:00:00:00/36`, are skipped.
+fn parse_prefix(prefix: &str) -> Option<[u8; 3]> {
+    let bytes: Vec<u8> = prefix
+        .split([':', '-'])
+        .map(|byte| match byte.len() {
+            2 => u8::from_str_radix(byte, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    bytes.try_into().ok()
+}
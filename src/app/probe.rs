@@ -0,0 +1,277 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    fmt, io,
+    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    process,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::app::config::{Probe, ProbeMethod};
+
+pub const DEFAULT_TCP_PORT: u16 = 22;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long a single probe attempt may take.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Pause between attempts while waiting for a machine to come up.
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+pub enum VerifyOutcome {
+    Up(Duration),
+    TimedOut(Duration),
+    Failed(String),
+}
+
+impl fmt::Display for VerifyOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyOutcome::Up(elapsed) => write!(f, "up after {}s", elapsed.as_secs()),
+            VerifyOutcome::TimedOut(elapsed) => {
+                write!(f, "timed out after {}s", elapsed.as_secs())
+            }
+            VerifyOutcome::Failed(err) => write!(f, "probe failed: {err}"),
+        }
+    }
+}
+
+/// A wake verification running on a background thread.
+pub struct Verification {
+    pub started: Instant,
+    pub timeout: Duration,
+    pub outcome: Option<VerifyOutcome>,
+    receiver: Receiver<VerifyOutcome>,
+}
+
+impl Verification {
+    pub fn spawn(probe: Probe) -> Verification {
+        let timeout = probe
+            .timeout_secs
+            .map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+        let started = Instant::now();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let outcome = loop {
+                match check(&probe, started) {
+                    Ok(true) => break VerifyOutcome::Up(started.elapsed()),
+                    Ok(false) => {}
+                    Err(err) => break VerifyOutcome::Failed(err.to_string()),
+                }
+                if started.elapsed() >= timeout {
+                    break VerifyOutcome::TimedOut(started.elapsed());
+                }
+                thread::sleep(RETRY_INTERVAL);
+            };
+            let _ = sender.send(outcome);
+        });
+
+        Verification {
+            started,
+            timeout,
+            outcome: None,
+            receiver,
+        }
+    }
+
+    /// Picks up the outcome once the background thread has finished.
+    pub fn poll(&mut self) {
+        if self.outcome.is_some() {
+            return;
+        }
+        match self.receiver.try_recv() {
+            Ok(outcome) => self.outcome = Some(outcome),
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => {
+                self.outcome = Some(VerifyOutcome::Failed("probe thread stopped".into()))
+            }
+        }
+    }
+}
+
+/// Probes the host once and reports whether it is up. For the `arp` method the host has
+/// to have answered since `since`.
+pub fn check(probe: &Probe, since: Instant) -> io::Result<bool> {
+    match probe.method {
+        ProbeMethod::Tcp => {
+            let addr = resolve(&probe.host, probe.port.unwrap_or(DEFAULT_TCP_PORT))?;
+            check_tcp(addr)
+        }
+        ProbeMethod::Icmp => check_icmp(resolve(&probe.host, 0)?.ip()),
+        ProbeMethod::Arp => check_arp(resolve(&probe.host, 0)?.ip(), since),
+    }
+}
+
+fn resolve(host: &str, port: u16) -> io::Result<SocketAddr> {
+    (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("cannot resolve host {host}"),
+        )
+    })
+}
+
+fn check_tcp(addr: SocketAddr) -> io::Result<bool> {
+    match TcpStream::connect_timeout(&addr, ATTEMPT_TIMEOUT) {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => Ok(true),
+        Err(_) => Ok(false),
+    }
+}
+
+fn check_icmp(ip: IpAddr) -> io::Result<bool> {
+    let (domain, protocol, request, reply) = match ip {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, 8, 0),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, 128, 129),
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(protocol)).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("ICMP echo is not permitted ({err}), check net.ipv4.ping_group_range"),
+        )
+    })?;
+    let socket = UdpSocket::from(socket);
+    socket.set_read_timeout(Some(ATTEMPT_TIMEOUT))?;
+
+    // type, code, checksum, identifier (set by the kernel), sequence number
+    let mut packet = [request, 0, 0, 0, 0, 0, 0, 1];
+    let checksum = icmp_checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    socket.send_to(&packet, SocketAddr::new(ip, 0))?;
+
+    let mut buf = [0; 64];
+    let deadline = Instant::now() + ATTEMPT_TIMEOUT;
+    while Instant::now() < deadline {
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) if len > 0 && from.ip() == ip && buf[0] == reply => return Ok(true),
+            Ok(_) => {}
+            Err(err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                return Ok(false)
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(false)
+}
+
+fn icmp_checksum(packet: &[u8]) -> u16 {
+    let mut sum: u32 = packet
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn check_arp(ip: IpAddr, since: Instant) -> io::Result<bool> {
+    // Any datagram makes the kernel resolve or revalidate the neighbor entry.
+    let local = match ip {
+        IpAddr::V4(_) => "0.0.0.0:0",
+        IpAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(local)?;
+    let _ = socket.send_to(&[0], SocketAddr::new(ip, 9));
+    thread::sleep(Duration::from_millis(200));
+
+    // `ip` reads the netlink neighbor table, which has IPv6 entries and, unlike
+    // `/proc/net/arp`, says when each entry was last confirmed.
+    let output = process::Command::new("ip")
+        .args(["-s", "neigh", "show"])
+        .arg(ip.to_string())
+        .output()
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("arp probes need the ip command ({err})"),
+            )
+        })?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(confirmed_secs_ago(&String::from_utf8_lossy(&output.stdout))
+        .is_some_and(|age| age <= since.elapsed().as_secs()))
+}
+
+/// Seconds since the neighbor in `ip -s neigh show` output last answered, `None` when
+/// there is no resolved entry. Entries stay in the table for minutes after a machine
+/// went to sleep, so an entry alone doesn't mean it is up.
+fn confirmed_secs_ago(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if !columns.contains(&"lladdr")
+            || columns.contains(&"FAILED")
+            || columns.contains(&"INCOMPLETE")
+        {
+            return None;
+        }
+        // `used 12/3/12`: seconds since it was used, confirmed and updated.
+        let used = columns.iter().position(|column| *column == "used")?;
+        columns.get(used + 1)?.split('/').nth(1)?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn tcp_probe(host: &str, port: u16) -> Probe {
+        Probe {
+            host: host.to_string(),
+            method: ProbeMethod::Tcp,
+            port: Some(port),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tcp_probe_reports_a_listening_port_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(check(&tcp_probe("127.0.0.1", port), Instant::now()).unwrap());
+    }
+
+    #[test]
+    fn neighbor_confirmation_age_is_read_from_ip_neigh() {
+        assert_eq!(
+            confirmed_secs_ago(
+                "192.168.1.10 dev eth0 lladdr 52:54:00:12:34:56 ref 1 used 40/0/35 probes 1 REACHABLE\n"
+            ),
+            Some(0)
+        );
+        assert_eq!(
+            confirmed_secs_ago(
+                "fe80::1 dev eth0 lladdr 52:54:00:12:34:56 router used 310/275/270 probes 0 STALE\n"
+            ),
+            Some(275)
+        );
+    }
+
+    #[test]
+    fn unresolved_neighbors_have_no_confirmation() {
+        assert_eq!(confirmed_secs_ago(""), None);
+        assert_eq!(
+            confirmed_secs_ago("192.168.1.11 dev eth0  used 3/3/0 probes 6 FAILED\n"),
+            None
+        );
+        assert_eq!(
+            confirmed_secs_ago("192.168.1.12 dev eth0  used 0/0/0 probes 1 INCOMPLETE\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn tcp_probe_reports_an_unroutable_host_down() {
+        // TEST-NET-1 is reserved for documentation and never answers.
+        assert!(!check(&tcp_probe("192.0.2.1", 9), Instant::now()).unwrap());
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::app::{
+    config::{Machine, UNTAGGED},
+    fuzzy::fuzzy_match,
+};
+
+/// A line of the rendered machine list.
+pub enum Row {
+    Group {
+        name: String,
+        members: usize,
+        collapsed: bool,
+    },
+    Machine {
+        /// Index into the unfiltered machine list.
+        index: usize,
+        group: Option<String>,
+    },
+}
+
+impl Row {
+    pub fn group(&self) -> Option<&str> {
+        match self {
+            Row::Group { name, .. } => Some(name),
+            Row::Machine { group, .. } => group.as_deref(),
+        }
+    }
+}
+
+/// Lists the machines matching `filter` in order, or in collapsible sections per tag when
+/// `grouped`. A machine with several tags shows up in each of their sections.
+pub fn build_rows(
+    machines: &[Machine],
+    grouped: bool,
+    collapsed: &HashSet<String>,
+    filter: &str,
+) -> Vec<Row> {
+    let visible = machines
+        .iter()
+        .enumerate()
+        .filter(|(_, machine)| matches_filter(machine, filter));
+
+    if !grouped {
+        return visible
+            .map(|(index, _)| Row::Machine { index, group: None })
+            .collect();
+    }
+
+    let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut untagged = vec![];
+    for (index, machine) in visible {
+        if machine.tags.is_empty() {
+            untagged.push(index);
+        }
+        for tag in &machine.tags {
+            groups.entry(tag.as_str()).or_default().push(index);
+        }
+    }
+
+    let mut rows = vec![];
+    let sections = groups
+        .into_iter()
+        .chain((!untagged.is_empty()).then_some((UNTAGGED, untagged)));
+    for (name, members) in sections {
+        let is_collapsed = collapsed.contains(name);
+        rows.push(Row::Group {
+            name: name.to_string(),
+            members: members.len(),
+            collapsed: is_collapsed,
+        });
+        if !is_collapsed {
+            rows.extend(members.into_iter().map(|index| Row::Machine {
+                index,
+                group: Some(name.to_string()),
+            }));
+        }
+    }
+    rows
+}
+
+pub fn matches_filter(machine: &Machine, filter: &str) -> bool {
+    filter.is_empty()
+        || fuzzy_match(filter, &machine.name).is_some()
+        || fuzzy_match(filter, &machine.mac_address.to_string()).is_some()
+        || machine
+            .tags
+            .iter()
+            .any(|tag| fuzzy_match(filter, tag).is_some())
+}
+
+/// Indexes of the machines belonging to `group`.
+pub fn group_members(machines: &[Machine], group: &str) -> Vec<usize> {
+    machines
+        .iter()
+        .enumerate()
+        .filter(|(_, machine)| machine.in_group(group))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::test_machine;
+
+    fn machines() -> Vec<Machine> {
+        let tagged = |name, last, tags: &[&str]| Machine {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..test_machine(name, last)
+        };
+        vec![
+            tagged("desktop", 1, &[]),
+            tagged("nas", 2, &["rack"]),
+            tagged("router", 3, &["rack", "network"]),
+            tagged("laptop", 4, &[]),
+        ]
+    }
+
+    fn indexes(rows: &[Row]) -> Vec<usize> {
+        rows.iter()
+            .filter_map(|row| match row {
+                Row::Machine { index, .. } => Some(*index),
+                Row::Group { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn filtered_rows_point_into_the_unfiltered_list() {
+        let machines = machines();
+        let rows = build_rows(&machines, false, &HashSet::new(), "la");
+        assert_eq!(indexes(&rows), [3]);
+        assert_eq!(machines[indexes(&rows)[0]].name, "laptop");
+
+        let rows = build_rows(&machines, false, &HashSet::new(), "rou");
+        assert_eq!(indexes(&rows), [2]);
+    }
+
+    #[test]
+    fn grouped_rows_keep_indexes_with_collapsed_sections() {
+        let machines = machines();
+        let collapsed = HashSet::from(["network".to_string()]);
+        let rows = build_rows(&machines, true, &collapsed, "");
+
+        let layout: Vec<String> = rows
+            .iter()
+            .map(|row| match row {
+                Row::Group {
+                    name,
+                    members,
+                    collapsed,
+                } => format!("{name} {members} {collapsed}"),
+                Row::Machine { index, group } => {
+                    format!("{} in {}", machines[*index].name, group.as_deref().unwrap())
+                }
+            })
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                "network 1 true",
+                "rack 2 false",
+                "nas in rack",
+                "router in rack",
+                "untagged 2 false",
+                "desktop in untagged",
+                "laptop in untagged",
+            ]
+        );
+        assert_eq!(indexes(&rows), [1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn grouped_rows_with_filter_skip_empty_sections() {
+        let machines = machines();
+        let collapsed = HashSet::from(["rack".to_string()]);
+        let rows = build_rows(&machines, true, &collapsed, "lap");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].group(), Some(UNTAGGED));
+        assert_eq!(indexes(&rows), [3]);
+    }
+}
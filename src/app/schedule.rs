@@ -0,0 +1,273 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    app::{
+        config::{Machine, Schedule},
+        rows,
+    },
+    error::{Error, Result},
+};
+
+/// A schedule with its cron expression and timezone parsed.
+pub struct Parsed {
+    cron: cron::Schedule,
+    timezone: Option<Tz>,
+}
+
+impl Parsed {
+    pub fn new(schedule: &Schedule) -> Result<Parsed> {
+        let expression = cron_expression(schedule)?;
+        let cron = cron::Schedule::from_str(&expression)
+            .map_err(|err| Error::Parse(format!("invalid schedule {expression}: {err}")))?;
+        let timezone = schedule
+            .timezone
+            .as_deref()
+            .map(|timezone| {
+                timezone
+                    .parse::<Tz>()
+                    .map_err(|err| Error::Parse(format!("invalid timezone {timezone}: {err}")))
+            })
+            .transpose()?;
+        Ok(Parsed { cron, timezone })
+    }
+
+    /// The first time the schedule fires after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.timezone {
+            Some(timezone) => next_in(&self.cron, &timezone, after),
+            None => next_in(&self.cron, &Local, after),
+        }
+    }
+}
+
+fn next_in<Z: TimeZone>(
+    cron: &cron::Schedule,
+    timezone: &Z,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    cron.after(&after.with_timezone(timezone))
+        .next()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// The schedule as a cron expression with seconds, as the `cron` crate expects. Five
+/// field expressions and `days` plus `time` fire at second 0.
+fn cron_expression(schedule: &Schedule) -> Result<String> {
+    match (&schedule.cron, &schedule.time) {
+        (Some(cron), None) if cron.split_whitespace().count() == 5 => {
+            let fields: Vec<&str> = cron.split_whitespace().collect();
+            Ok(format!(
+                "0 {} {}",
+                fields[..4].join(" "),
+                crontab_days(fields[4])?
+            ))
+        }
+        (Some(cron), None) => Ok(cron.clone()),
+        (None, Some(time)) => {
+            let (hour, minute) = time
+                .split_once(':')
+                .and_then(|(hour, minute)| {
+                    Some((hour.parse::<u8>().ok()?, minute.parse::<u8>().ok()?))
+                })
+                .filter(|(hour, minute)| *hour < 24 && *minute < 60)
+                .ok_or_else(|| {
+                    Error::Parse(format!("invalid schedule time {time}, expected HH:MM"))
+                })?;
+            let days = schedule
+                .days
+                .iter()
+                .map(|day| day_field(day))
+                .collect::<Result<Vec<_>>>()?;
+            let days = if days.is_empty() {
+                "*".to_string()
+            } else {
+                days.join(",")
+            };
+            Ok(format!("0 {minute} {hour} * * {days}"))
+        }
+        _ => Err(Error::Parse(
+            "a schedule needs either cron or time, not both".to_string(),
+        )),
+    }
+}
+
+const DAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Converts a crontab day of week field, where 0 and 7 are Sunday, to day names, since
+/// the `cron` crate numbers days from 1 for Sunday. Names are kept as they are.
+fn crontab_days(field: &str) -> Result<String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_string());
+    }
+    let invalid = || Error::Parse(format!("invalid day of week {field}"));
+    let mut parts = vec![];
+    let mut days = [false; 7];
+    for part in field.split(',') {
+        if part.chars().any(|c| c.is_ascii_alphabetic()) {
+            parts.push(part.to_string());
+            continue;
+        }
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let bounds = match range.split_once('-') {
+            _ if range == "*" => Some((0, 6)),
+            Some((first, last)) => first.parse().ok().zip(last.parse().ok()),
+            // `5/2` runs to the end of the week, like `5-7/2`.
+            None if step > 1 => range.parse().ok().map(|first| (first, 7)),
+            None => range.parse().ok().map(|day| (day, day)),
+        };
+        let (first, last): (usize, usize) = bounds
+            .filter(|(first, last)| first <= last && *last <= 7 && step > 0)
+            .ok_or_else(invalid)?;
+        for day in (first..=last).step_by(step) {
+            days[day % 7] = true;
+        }
+    }
+    parts.extend(
+        DAY_NAMES
+            .iter()
+            .zip(days)
+            .filter(|(_, included)| *included)
+            .map(|(name, _)| name.to_string()),
+    );
+    Ok(parts.join(","))
+}
+
+fn day_field(day: &str) -> Result<&'static str> {
+    Ok(match day.to_ascii_lowercase().as_str() {
+        "weekdays" => "Mon-Fri",
+        "weekends" => "Sat,Sun",
+        "mon" | "monday" => "Mon",
+        "tue" | "tuesday" => "Tue",
+        "wed" | "wednesday" => "Wed",
+        "thu" | "thursday" => "Thu",
+        "fri" | "friday" => "Fri",
+        "sat" | "saturday" => "Sat",
+        "sun" | "sunday" => "Sun",
+        _ => return Err(Error::Parse(format!("invalid schedule day {day}"))),
+    })
+}
+
+/// The next scheduled wake after `after` of each machine, by name. Invalid schedules are
+/// left out.
+pub fn next_wakes(
+    schedules: &[Schedule],
+    machines: &[Machine],
+    after: DateTime<Utc>,
+) -> HashMap<String, DateTime<Utc>> {
+    let mut next_wakes: HashMap<String, DateTime<Utc>> = HashMap::new();
+    for schedule in schedules {
+        let Some(next) = Parsed::new(schedule)
+            .ok()
+            .and_then(|parsed| parsed.next_after(after))
+        else {
+            continue;
+        };
+        for index in targets(schedule, machines) {
+            let wake = next_wakes
+                .entry(machines[index].name.clone())
+                .or_insert(next);
+            *wake = (*wake).min(next);
+        }
+    }
+    next_wakes
+}
+
+/// Indices into `machines` woken by `schedule`.
+pub fn targets(schedule: &Schedule, machines: &[Machine]) -> Vec<usize> {
+    let mut targets: Vec<usize> = machines
+        .iter()
+        .enumerate()
+        .filter(|(_, machine)| schedule.machine.as_ref() == Some(&machine.name))
+        .map(|(index, _)| index)
+        .collect();
+    if let Some(group) = &schedule.group {
+        targets.extend(rows::group_members(machines, group));
+    }
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Timelike, Weekday};
+
+    fn cron(expression: &str) -> Schedule {
+        Schedule {
+            cron: Some(expression.to_string()),
+            timezone: Some("UTC".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// The weekdays the schedule fires on in the week after Saturday 2024-06-01.
+    fn weekdays(schedule: &Schedule) -> Vec<Weekday> {
+        let parsed = Parsed::new(schedule).unwrap();
+        let mut after = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let end = after + chrono::Duration::days(7);
+        let mut weekdays = vec![];
+        while let Some(next) = parsed.next_after(after).filter(|next| *next < end) {
+            weekdays.push(next.weekday());
+            after = next;
+        }
+        weekdays
+    }
+
+    #[test]
+    fn crontab_weekdays_start_on_monday() {
+        let parsed = Parsed::new(&cron("30 7 * * 1-5")).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let next = parsed.next_after(after).unwrap();
+        assert_eq!(next.weekday(), Weekday::Mon);
+        assert_eq!((next.hour(), next.minute(), next.second()), (7, 30, 0));
+        assert_eq!(
+            weekdays(&cron("30 7 * * 1-5")),
+            [
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri
+            ]
+        );
+    }
+
+    #[test]
+    fn crontab_sunday_is_0_or_7() {
+        assert_eq!(weekdays(&cron("0 9 * * 0")), [Weekday::Sun]);
+        assert_eq!(weekdays(&cron("0 9 * * 7")), [Weekday::Sun]);
+        assert_eq!(
+            weekdays(&cron("0 9 * * 5-7")),
+            [Weekday::Sun, Weekday::Fri, Weekday::Sat]
+        );
+    }
+
+    #[test]
+    fn crontab_day_fields_are_converted_to_names() {
+        assert_eq!(crontab_days("*").unwrap(), "*");
+        assert_eq!(crontab_days("1-5").unwrap(), "Mon,Tue,Wed,Thu,Fri");
+        assert_eq!(crontab_days("*/2").unwrap(), "Sun,Tue,Thu,Sat");
+        assert_eq!(crontab_days("0,6").unwrap(), "Sun,Sat");
+        assert_eq!(crontab_days("MON-FRI").unwrap(), "MON-FRI");
+        assert!(crontab_days("8").is_err());
+        assert!(crontab_days("5-1").is_err());
+        assert!(crontab_days("1-5/0").is_err());
+    }
+
+    #[test]
+    fn days_and_time_fire_on_the_given_days() {
+        let schedule = Schedule {
+            days: vec!["weekends".to_string()],
+            time: Some("08:15".to_string()),
+            timezone: Some("UTC".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(weekdays(&schedule), [Weekday::Sun, Weekday::Sat]);
+    }
+}
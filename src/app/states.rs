@@ -2,12 +2,18 @@ use sm::sm;
 
 sm! {
   AppSM {
-    InitialStates { Main }
+    InitialStates { Main, ConfigInvalid }
 
     Add {
       Main => NameInput
     }
 
+    Edit {
+      Main => NameInput
+      SecureOnInput => ConfirmEdit
+      Discovering => DiscoverName
+    }
+
     Delete {
       Main => ConfirmDelete
     }
@@ -16,11 +22,73 @@ sm! {
       Main => SendPop
     }
 
+    Search {
+      Main => Filter
+    }
+
+    WakeGroup {
+      Main => GroupSendPop
+    }
+
+    Discover {
+      Main => Discovering
+    }
+
+    Transfer {
+      Main => TransferMenu
+    }
+
+    Conflict {
+      Main => ConflictPop
+    }
+
+    History {
+      Main => HistoryView
+    }
+
+    Import {
+      TransferMenu => ImportPath
+      ImportPath => ImportPreview
+    }
+
+    Export {
+      TransferMenu => ExportPath
+    }
+
+    Verify {
+      SendPop => Verifying
+    }
+
+    Fail {
+      Main => ErrorPop
+      SendPop => ErrorPop
+      ImportPath => ErrorPop
+      ExportPath => ErrorPop
+    }
+
     Cancel {
       NameInput => Main
       MacInput => Main
+      TargetInput => Main
+      PortInput => Main
+      BindInput => Main
+      TagsInput => Main
+      SecureOnInput => Main
       ConfirmAdd => Main
+      ConfirmEdit => Main
       ConfirmDelete => Main
+      Verifying => Main
+      GroupSendPop => Main
+      ErrorPop => Main
+      Filter => Main
+      Discovering => Main
+      DiscoverName => Discovering
+      TransferMenu => Main
+      ImportPath => Main
+      ImportPreview => Main
+      ExportPath => Main
+      ConflictPop => Main
+      HistoryView => Main
     }
 
     Exit {
@@ -28,11 +96,27 @@ sm! {
     }
 
     Next {
+      ConfigInvalid => Main
       NameInput => MacInput
-      MacInput => ConfirmAdd
+      MacInput => TargetInput
+      TargetInput => PortInput
+      PortInput => BindInput
+      BindInput => TagsInput
+      TagsInput => SecureOnInput
+      SecureOnInput => ConfirmAdd
       ConfirmAdd => Main
+      ConfirmEdit => Main
       ConfirmDelete => Main
       SendPop => Main
+      Verifying => Main
+      GroupSendPop => Main
+      ErrorPop => Main
+      Filter => Main
+      Discovering => Main
+      DiscoverName => Discovering
+      ImportPreview => Main
+      ExportPath => Main
+      ConflictPop => Main
     }
   }
 }
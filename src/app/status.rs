@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::app::{config::Machine, probe};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often the poller thread checks whether it has been dropped while sleeping.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy)]
+pub struct Status {
+    pub online: bool,
+    pub last_seen: Option<Instant>,
+}
+
+/// Probes every machine with a `probe` on a background thread, keyed by machine name.
+pub struct StatusPoller {
+    statuses: Arc<Mutex<HashMap<String, Status>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl StatusPoller {
+    pub fn spawn(machines: &[Machine], interval: Duration) -> StatusPoller {
+        let targets: Vec<_> = machines
+            .iter()
+            .filter_map(|m| m.probe.clone().map(|probe| (m.name.clone(), probe)))
+            .collect();
+        let statuses = Arc::new(Mutex::new(HashMap::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_statuses = Arc::clone(&statuses);
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            // Neighbor table answers count when they came after the previous round.
+            let mut since = Instant::now()
+                .checked_sub(interval)
+                .unwrap_or_else(Instant::now);
+            while !targets.is_empty() && !thread_stop.load(Ordering::Relaxed) {
+                let round = Instant::now();
+                let results: Vec<(String, bool)> = thread::scope(|scope| {
+                    let handles: Vec<_> = targets
+                        .iter()
+                        .map(|(name, probe)| {
+                            scope.spawn(move || {
+                                (name.clone(), probe::check(probe, since).unwrap_or(false))
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .filter_map(|handle| handle.join().ok())
+                        .collect()
+                });
+
+                let now = Instant::now();
+                let mut statuses = thread_statuses.lock().unwrap();
+                for (name, online) in results {
+                    let last_seen = statuses.get(&name).and_then(|s: &Status| s.last_seen);
+                    let status = Status {
+                        online,
+                        last_seen: if online { Some(now) } else { last_seen },
+                    };
+                    statuses.insert(name, status);
+                }
+                drop(statuses);
+                since = round;
+
+                let started = Instant::now();
+                while started.elapsed() < interval && !thread_stop.load(Ordering::Relaxed) {
+                    thread::sleep(STOP_CHECK_INTERVAL);
+                }
+            }
+        });
+
+        StatusPoller { statuses, stop }
+    }
+
+    pub fn status(&self, name: &str) -> Option<Status> {
+        self.statuses.lock().unwrap().get(name).copied()
+    }
+}
+
+impl Drop for StatusPoller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn format_ago(elapsed: Duration) -> String {
+    match elapsed.as_secs() {
+        secs if secs < 60 => format!("{secs}s ago"),
+        secs if secs < 60 * 60 => format!("{}m ago", secs / 60),
+        secs if secs < 24 * 60 * 60 => format!("{}h ago", secs / (60 * 60)),
+        secs => format!("{}d ago", secs / (24 * 60 * 60)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::{Probe, ProbeMethod};
+    use std::net::TcpListener;
+
+    fn machine(name: &str, host: &str, port: u16) -> Machine {
+        Machine {
+            name: name.to_string(),
+            probe: Some(Probe {
+                host: host.to_string(),
+                method: ProbeMethod::Tcp,
+                port: Some(port),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Waits for the poller's first round to report `name`.
+    fn wait_for(poller: &StatusPoller, name: &str) -> Status {
+        let started = Instant::now();
+        loop {
+            if let Some(status) = poller.status(name) {
+                return status;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10), "no status");
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn listening_machine_is_online() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let poller = StatusPoller::spawn(&[machine("up", "127.0.0.1", port)], DEFAULT_INTERVAL);
+
+        let status = wait_for(&poller, "up");
+        assert!(status.online);
+        assert!(status.last_seen.is_some());
+    }
+
+    #[test]
+    fn unroutable_machine_is_offline() {
+        let poller = StatusPoller::spawn(&[machine("down", "192.0.2.1", 9)], DEFAULT_INTERVAL);
+
+        let status = wait_for(&poller, "down");
+        assert!(!status.online);
+        assert!(status.last_seen.is_none());
+    }
+
+    #[test]
+    fn format_ago_picks_the_largest_unit() {
+        assert_eq!(format_ago(Duration::from_secs(59)), "59s ago");
+        assert_eq!(format_ago(Duration::from_secs(60 * 5)), "5m ago");
+        assert_eq!(format_ago(Duration::from_secs(60 * 60 * 3)), "3h ago");
+        assert_eq!(format_ago(Duration::from_secs(60 * 60 * 24 * 2)), "2d ago");
+    }
+}
@@ -0,0 +1,419 @@
+mod ethernet;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV6, ToSocketAddrs},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    app::config::{Config, MacAddress, Machine, SecureOn, Target, Transport},
+    error::{Error, Result},
+};
+
+pub const DEFAULT_PORT: u16 = 9;
+pub const DEFAULT_REPEAT: u32 = 1;
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many packets to send per wake and how long to wait between them.
+#[derive(Debug, Clone, Copy)]
+pub struct Burst {
+    pub repeat: u32,
+    pub interval: Duration,
+}
+
+impl Burst {
+    /// The machine's `repeat` and `interval_ms`, falling back to the global settings.
+    pub fn for_machine(config: &Config, machine: &Machine) -> Burst {
+        Burst {
+            repeat: machine
+                .repeat
+                .or(config.repeat)
+                .unwrap_or(DEFAULT_REPEAT)
+                .max(1),
+            interval: machine
+                .interval_ms
+                .or(config.interval_ms)
+                .map_or(DEFAULT_INTERVAL, Duration::from_millis),
+        }
+    }
+}
+
+enum JobEvent {
+    Sent { index: usize, sent: u32 },
+    Done { index: usize, result: Result<()> },
+}
+
+pub struct JobEntry {
+    pub machine: Machine,
+    pub total: u32,
+    pub sent: u32,
+    pub result: Option<Result<()>>,
+}
+
+/// Bursts to one or more machines running on background threads.
+pub struct SendJob {
+    pub entries: Vec<JobEntry>,
+    receiver: Receiver<JobEvent>,
+}
+
+impl SendJob {
+    pub fn spawn(machines: Vec<(Machine, Burst)>) -> SendJob {
+        let (sender, receiver) = mpsc::channel();
+        let entries = machines
+            .iter()
+            .map(|(machine, burst)| JobEntry {
+                machine: machine.clone(),
+                total: burst.repeat,
+                sent: 0,
+                result: None,
+            })
+            .collect();
+
+        for (index, (machine, burst)) in machines.into_iter().enumerate() {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                let result = send_burst(&machine, burst, |sent| {
+                    let _ = sender.send(JobEvent::Sent { index, sent });
+                });
+                let _ = sender.send(JobEvent::Done { index, result });
+            });
+        }
+
+        SendJob { entries, receiver }
+    }
+
+    /// Applies the progress reported by the sending threads, returning the entries that
+    /// finished since the last poll.
+    pub fn poll(&mut self) -> Vec<usize> {
+        let mut finished = vec![];
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                JobEvent::Sent { index, sent } => self.entries[index].sent = sent,
+                JobEvent::Done { index, result } => {
+                    self.entries[index].result = Some(result);
+                    finished.push(index);
+                }
+            }
+        }
+        finished
+    }
+
+    pub fn finished(&self) -> bool {
+        self.entries.iter().all(|entry| entry.result.is_some())
+    }
+
+    /// Takes the first failure out of the job.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.entries
+            .iter_mut()
+            .find(|entry| matches!(entry.result, Some(Err(_))))
+            .and_then(|entry| entry.result.take())
+            .and_then(|result| result.err())
+    }
+}
+
+/// Builds the magic packet: six 0xff bytes followed by the MAC repeated 16 times, 102
+/// bytes in total, with the SecureOn password appended for 106 or 108 bytes.
+pub fn magic_packet(mac: &[u8; 6], secureon: Option<&[u8]>) -> Vec<u8> {
+    let mut packet = vec![0xff; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(mac);
+    }
+    if let Some(password) = secureon {
+        packet.extend_from_slice(password);
+    }
+    packet
+}
+
+/// The MAC address and SecureOn password of a magic packet, or `None` when `packet`
+/// isn't one.
+pub fn parse_magic_packet(packet: &[u8]) -> Option<(MacAddress, Option<&[u8]>)> {
+    if packet.len() < 102 || packet[..6] != [0xff; 6] {
+        return None;
+    }
+    let mac: [u8; 6] = packet[6..12].try_into().ok()?;
+    if !packet[6..102].chunks(6).all(|chunk| chunk == mac) {
+        return None;
+    }
+    match packet.len() {
+        102 => Some((MacAddress(mac), None)),
+        106 | 108 => Some((MacAddress(mac), Some(&packet[102..]))),
+        _ => None,
+    }
+}
+
+pub fn send(machine: &Machine) -> Result<()> {
+    deliver(machine, &packet_for(machine)).map(|_| ())
+}
+
+/// The magic packet for `machine`, with its SecureOn password.
+pub fn packet_for(machine: &Machine) -> Vec<u8> {
+    let secureon = machine.secureon.as_ref().map(SecureOn::bytes);
+    magic_packet(&machine.mac_address.0, secureon)
+}
+
+/// Sends an already built magic packet the way `machine` is configured to be woken,
+/// returning the local port it went out from when it was sent over UDP.
+pub fn deliver(machine: &Machine, packet: &[u8]) -> Result<Option<u16>> {
+    if let Some(via) = &machine.via {
+        return relay_addr(via)
+            .and_then(|relay| send_packet(packet, relay, machine.bind.as_deref()))
+            .map(Some)
+            .map_err(Error::Network);
+    }
+    let sent = match machine.transport {
+        Transport::Udp => socket_addr(
+            machine.target.as_ref(),
+            machine.port.unwrap_or(DEFAULT_PORT),
+        )
+        .and_then(|target| send_packet(packet, target, machine.bind.as_deref()))
+        .map(Some),
+        Transport::Ethernet => {
+            ethernet::send_frame(machine.bind.as_deref(), [0xff; 6], packet).map(|()| None)
+        }
+    };
+    sent.map_err(Error::Network)
+}
+
+/// Where the packets for `machine` go, for display.
+pub fn target_label(machine: &Machine) -> String {
+    if let Some(via) = &machine.via {
+        return format!("relay {via}");
+    }
+    let port = machine.port.unwrap_or(DEFAULT_PORT);
+    match (machine.transport, &machine.target) {
+        (Transport::Ethernet, _) => format!(
+            "ethernet via {}",
+            machine.bind.as_deref().unwrap_or("default interface")
+        ),
+        (Transport::Udp, None) => format!("{}:{port}", Ipv4Addr::BROADCAST),
+        (Transport::Udp, Some(target)) if target.ip.is_ipv6() => format!("[{target}]:{port}"),
+        (Transport::Udp, Some(target)) => format!("{target}:{port}"),
+    }
+}
+
+/// Sends `burst.repeat` packets to `machine`, calling `on_sent` with the count so far.
+pub fn send_burst(machine: &Machine, burst: Burst, mut on_sent: impl FnMut(u32)) -> Result<()> {
+    for sent in 1..=burst.repeat {
+        send(machine)?;
+        on_sent(sent);
+        if sent < burst.repeat {
+            thread::sleep(burst.interval);
+        }
+    }
+    Ok(())
+}
+
+/// The destination for `target`, the IPv4 limited broadcast when unset.
+pub fn socket_addr(target: Option<&Target>, port: u16) -> io::Result<SocketAddr> {
+    let Some(target) = target else {
+        return Ok(SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), port));
+    };
+    match (target.ip, target.scope.as_deref()) {
+        (IpAddr::V6(ip), Some(scope)) => Ok(SocketAddr::V6(SocketAddrV6::new(
+            ip,
+            port,
+            0,
+            scope_id(scope)?,
+        ))),
+        (ip, _) => Ok(SocketAddr::new(ip, port)),
+    }
+}
+
+/// Resolves the `host:port` of a relay to its first address.
+fn relay_addr(via: &str) -> io::Result<SocketAddr> {
+    via.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("relay {via} has no address"),
+        )
+    })
+}
+
+/// Resolves an IPv6 scope given as an interface index or name.
+fn scope_id(scope: &str) -> io::Result<u32> {
+    if let Ok(index) = scope.parse() {
+        return Ok(index);
+    }
+    interface_index(scope)
+}
+
+#[cfg(target_os = "linux")]
+fn interface_index(interface: &str) -> io::Result<u32> {
+    std::fs::read_to_string(format!("/sys/class/net/{interface}/ifindex"))
+        .ok()
+        .and_then(|index| index.trim().parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such interface: {interface}"),
+            )
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn interface_index(interface: &str) -> io::Result<u32> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("use the numeric index of {interface} as IPv6 scope on this platform"),
+    ))
+}
+
+/// Sends `packet` from a fresh socket, returning its local port.
+fn send_packet(packet: &[u8], target: SocketAddr, bind: Option<&str>) -> io::Result<u16> {
+    let socket = Socket::new(
+        Domain::for_address(target),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    match target {
+        SocketAddr::V4(_) => socket.set_broadcast(true)?,
+        SocketAddr::V6(target) if target.ip().is_multicast() && target.scope_id() != 0 => {
+            socket.set_multicast_if_v6(target.scope_id())?
+        }
+        SocketAddr::V6(_) => {}
+    }
+    if let Some(bind) = bind {
+        match bind.parse::<IpAddr>() {
+            Ok(addr) => socket.bind(&SocketAddr::new(addr, 0).into())?,
+            Err(_) => bind_device(&socket, bind)?,
+        }
+    }
+
+    socket.send_to(packet, &target.into())?;
+    Ok(socket
+        .local_addr()?
+        .as_socket()
+        .map_or(0, |local| local.port()))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_device(socket: &Socket, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_device(_socket: &Socket, interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("binding to interface {interface} is not supported on this platform"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    fn expected_packet(trailer: &[u8]) -> Vec<u8> {
+        let mut expected = vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        for _ in 0..16 {
+            expected.extend_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        }
+        expected.extend_from_slice(trailer);
+        expected
+    }
+
+    #[test]
+    fn magic_packet_without_password_is_102_bytes() {
+        let packet = magic_packet(&MAC, None);
+        assert_eq!(packet.len(), 102);
+        assert_eq!(packet, expected_packet(&[]));
+    }
+
+    #[test]
+    fn magic_packet_with_4_byte_password_is_106_bytes() {
+        let password: SecureOn = "de:ad:be:ef".parse().unwrap();
+        let packet = magic_packet(&MAC, Some(password.bytes()));
+        assert_eq!(packet.len(), 106);
+        assert_eq!(packet, expected_packet(&[0xde, 0xad, 0xbe, 0xef]));
+    }
+
+    #[test]
+    fn magic_packet_with_6_byte_password_is_108_bytes() {
+        let password: SecureOn = "01-02-03-0a-0b-0c".parse().unwrap();
+        let packet = magic_packet(&MAC, Some(password.bytes()));
+        assert_eq!(packet.len(), 108);
+        assert_eq!(
+            packet,
+            expected_packet(&[0x01, 0x02, 0x03, 0x0a, 0x0b, 0x0c])
+        );
+    }
+
+    #[test]
+    fn socket_addr_defaults_to_limited_broadcast() {
+        assert_eq!(
+            socket_addr(None, 9).unwrap(),
+            "255.255.255.255:9".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn socket_addr_uses_numeric_scope_as_scope_id() {
+        let target: Target = "fe80::1%2".parse().unwrap();
+        match socket_addr(Some(&target), 7).unwrap() {
+            SocketAddr::V6(addr) => {
+                assert_eq!(addr.port(), 7);
+                assert_eq!(addr.scope_id(), 2);
+            }
+            addr => panic!("expected IPv6, got {addr}"),
+        }
+    }
+
+    #[test]
+    fn send_reaches_ipv6_unicast_target() {
+        let receiver = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let machine = Machine {
+            name: "loopback".into(),
+            mac_address: MacAddress(MAC),
+            target: Some("::1".parse().unwrap()),
+            port: Some(receiver.local_addr().unwrap().port()),
+            secureon: Some("de:ad:be:ef".parse().unwrap()),
+            ..Default::default()
+        };
+        send(&machine).unwrap();
+
+        let mut buffer = [0; 256];
+        let (len, _) = receiver.recv_from(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], expected_packet(&[0xde, 0xad, 0xbe, 0xef]));
+    }
+
+    #[test]
+    fn parse_magic_packet_round_trips() {
+        let packet = magic_packet(&MAC, None);
+        assert_eq!(parse_magic_packet(&packet), Some((MacAddress(MAC), None)));
+
+        let password = [0xde, 0xad, 0xbe, 0xef];
+        let packet = magic_packet(&MAC, Some(&password));
+        assert_eq!(
+            parse_magic_packet(&packet),
+            Some((MacAddress(MAC), Some(&password[..])))
+        );
+    }
+
+    #[test]
+    fn parse_magic_packet_rejects_other_payloads() {
+        let packet = magic_packet(&MAC, None);
+        assert_eq!(parse_magic_packet(&packet[..101]), None);
+
+        let mut corrupted = packet.clone();
+        corrupted[50] ^= 0x01;
+        assert_eq!(parse_magic_packet(&corrupted), None);
+
+        let mut no_sync = packet.clone();
+        no_sync[0] = 0;
+        assert_eq!(parse_magic_packet(&no_sync), None);
+
+        let mut five_byte_password = packet;
+        five_byte_password.extend_from_slice(&[1, 2, 3, 4, 5]);
+        assert_eq!(parse_magic_packet(&five_byte_password), None);
+    }
+}
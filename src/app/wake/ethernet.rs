@@ -0,0 +1,250 @@
+use std::io;
+
+/// EtherType registered for Wake-on-LAN frames.
+pub const ETHERTYPE_WOL: u16 = 0x0842;
+
+/// Sends `payload` in an Ethernet frame with EtherType 0x0842 to `destination` out of
+/// `interface`, or the interface of the default route when none is given.
+#[cfg(target_os = "linux")]
+pub fn send_frame(interface: Option<&str>, destination: [u8; 6], payload: &[u8]) -> io::Result<()> {
+    use std::ffi::CString;
+
+    let interface = match interface {
+        Some(interface) => interface.to_string(),
+        None => default_interface()?,
+    };
+    let name = CString::new(interface.as_str())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+    // SAFETY: `name` is a valid NUL terminated string.
+    let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if ifindex == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no such interface: {interface}"),
+        ));
+    }
+    let source = interface_mac(&interface)?;
+
+    transmit::<RawSocket>(ifindex as libc::c_int, destination, source, payload)
+}
+
+/// An `AF_PACKET` socket, behind a trait so sending can be tested without CAP_NET_RAW.
+#[cfg(target_os = "linux")]
+trait PacketSocket: Sized {
+    fn open() -> io::Result<Self>;
+    fn send_to(&self, frame: &[u8], ifindex: libc::c_int, destination: [u8; 6]) -> io::Result<()>;
+}
+
+#[cfg(target_os = "linux")]
+fn transmit<S: PacketSocket>(
+    ifindex: libc::c_int,
+    destination: [u8; 6],
+    source: [u8; 6],
+    payload: &[u8],
+) -> io::Result<()> {
+    let socket = S::open().map_err(|err| match err.kind() {
+        io::ErrorKind::PermissionDenied => io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "raw Ethernet wake needs CAP_NET_RAW, run as root or \
+             `setcap cap_net_raw+ep` the woltui binary",
+        ),
+        _ => err,
+    })?;
+    socket.send_to(
+        &ethernet_frame(destination, source, payload),
+        ifindex,
+        destination,
+    )
+}
+
+#[cfg(target_os = "linux")]
+struct RawSocket(std::os::fd::OwnedFd);
+
+#[cfg(target_os = "linux")]
+impl PacketSocket for RawSocket {
+    fn open() -> io::Result<RawSocket> {
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        // SAFETY: plain socket(2) call, the descriptor is owned right after.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW,
+                ETHERTYPE_WOL.to_be() as libc::c_int,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a freshly opened descriptor nothing else owns.
+        Ok(RawSocket(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    fn send_to(&self, frame: &[u8], ifindex: libc::c_int, destination: [u8; 6]) -> io::Result<()> {
+        use std::{mem, os::fd::AsRawFd};
+
+        // SAFETY: sockaddr_ll is plain old data, all zeroes is a valid value.
+        let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as libc::c_ushort;
+        addr.sll_protocol = ETHERTYPE_WOL.to_be();
+        addr.sll_ifindex = ifindex;
+        addr.sll_halen = 6;
+        addr.sll_addr[..6].copy_from_slice(&destination);
+
+        // SAFETY: `frame` and `addr` outlive the call and the lengths match them.
+        let sent = unsafe {
+            libc::sendto(
+                self.0.as_raw_fd(),
+                frame.as_ptr() as *const libc::c_void,
+                frame.len(),
+                0,
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn send_frame(
+    _interface: Option<&str>,
+    _destination: [u8; 6],
+    _payload: &[u8],
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "raw Ethernet wake is only supported on Linux",
+    ))
+}
+
+/// Destination and source MAC, EtherType, then the payload.
+#[cfg(target_os = "linux")]
+fn ethernet_frame(destination: [u8; 6], source: [u8; 6], payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    frame.extend_from_slice(&destination);
+    frame.extend_from_slice(&source);
+    frame.extend_from_slice(&ETHERTYPE_WOL.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[cfg(target_os = "linux")]
+fn interface_mac(interface: &str) -> io::Result<[u8; 6]> {
+    let address = std::fs::read_to_string(format!("/sys/class/net/{interface}/address"))?;
+    address
+        .trim()
+        .parse::<crate::app::config::MacAddress>()
+        .map(|mac| mac.0)
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("cannot read the MAC address of {interface}"),
+            )
+        })
+}
+
+/// The interface of the IPv4 default route in `/proc/net/route`.
+#[cfg(target_os = "linux")]
+fn default_interface() -> io::Result<String> {
+    let routes = std::fs::read_to_string("/proc/net/route")?;
+    routes
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|columns| columns.len() > 1 && columns[1] == "00000000")
+        .map(|columns| columns[0].to_string())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "no default route, set `bind` to the interface to send from",
+            )
+        })
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const DESTINATION: [u8; 6] = [0xff; 6];
+    const SOURCE: [u8; 6] = [0x02, 0x00, 0x00, 0xaa, 0xbb, 0xcc];
+
+    thread_local! {
+        static SENT: RefCell<Vec<(Vec<u8>, libc::c_int, [u8; 6])>> = RefCell::new(vec![]);
+    }
+
+    struct Recording;
+
+    impl PacketSocket for Recording {
+        fn open() -> io::Result<Recording> {
+            Ok(Recording)
+        }
+
+        fn send_to(
+            &self,
+            frame: &[u8],
+            ifindex: libc::c_int,
+            destination: [u8; 6],
+        ) -> io::Result<()> {
+            SENT.with(|sent| {
+                sent.borrow_mut()
+                    .push((frame.to_vec(), ifindex, destination))
+            });
+            Ok(())
+        }
+    }
+
+    struct Unprivileged;
+
+    impl PacketSocket for Unprivileged {
+        fn open() -> io::Result<Unprivileged> {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        }
+
+        fn send_to(&self, _: &[u8], _: libc::c_int, _: [u8; 6]) -> io::Result<()> {
+            unreachable!("never opened")
+        }
+    }
+
+    #[test]
+    fn frame_has_addresses_and_wol_ethertype_before_payload() {
+        let frame = ethernet_frame(DESTINATION, SOURCE, &[1, 2, 3]);
+        assert_eq!(
+            frame,
+            [
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // destination
+                0x02, 0x00, 0x00, 0xaa, 0xbb, 0xcc, // source
+                0x08, 0x42, // EtherType
+                1, 2, 3,
+            ]
+        );
+    }
+
+    #[test]
+    fn transmit_sends_the_frame_to_the_interface() {
+        let payload = crate::app::wake::magic_packet(&[0, 0x11, 0x22, 0x33, 0x44, 0x55], None);
+        transmit::<Recording>(3, DESTINATION, SOURCE, &payload).unwrap();
+
+        SENT.with(|sent| {
+            let sent = sent.borrow();
+            assert_eq!(sent.len(), 1);
+            let (frame, ifindex, destination) = &sent[0];
+            assert_eq!(frame.len(), 14 + 102);
+            assert_eq!(&frame[12..14], [0x08, 0x42]);
+            assert_eq!(&frame[14..], payload);
+            assert_eq!(*ifindex, 3);
+            assert_eq!(*destination, DESTINATION);
+        });
+    }
+
+    #[test]
+    fn permission_denied_explains_cap_net_raw() {
+        let err = transmit::<Unprivileged>(3, DESTINATION, SOURCE, &[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("CAP_NET_RAW"));
+    }
+}
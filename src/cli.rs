@@ -0,0 +1,322 @@
+use clap::{Parser, Subcommand};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    app::{
+        config, history,
+        inventory::{self, Format, ImportMode},
+        wake,
+    },
+    daemon, mqtt, relay, serve,
+};
+
+/// Exit code when a wake, lookup or removal failed.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code for invalid arguments, matching clap's own usage errors.
+const EXIT_USAGE: i32 = 2;
+/// Exit code when the config file cannot be located, read or written.
+pub const EXIT_CONFIG: i32 = 3;
+
+#[derive(Parser)]
+#[command(version, about = "Wake-on-LAN from a TUI or the command line")]
+pub struct Cli {
+    /// Config file to use instead of the default location
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Send a magic packet to machines given by name or MAC address
+    Wake {
+        #[arg(required_unless_present = "group", value_name = "NAME|MAC")]
+        machines: Vec<String>,
+        /// Also wake every machine tagged with this group
+        #[arg(long, short)]
+        group: Vec<String>,
+    },
+    /// List configured machines
+    List,
+    /// Add a machine to the config
+    Add { name: String, mac: String },
+    /// Remove a machine from the config
+    Remove { name: String },
+    /// Import machines from an ethers, CSV or JSON file
+    Import {
+        path: PathBuf,
+        /// File format, guessed from the extension when omitted
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        #[arg(long, value_enum, default_value_t = ImportMode::Merge)]
+        mode: ImportMode,
+        /// Show what would be imported without saving
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export machines to an ethers, CSV or JSON file
+    Export {
+        /// Output file, standard output when omitted
+        path: Option<PathBuf>,
+        /// File format, guessed from the extension when omitted
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Run headless and wake machines on the configured schedules
+    Daemon,
+    /// Serve a JSON API for listing and waking machines
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080", value_name = "ADDR")]
+        listen: SocketAddr,
+    },
+    /// Wake machines on MQTT commands and publish their state
+    Mqtt,
+    /// Rebroadcast magic packets for configured machines into the local subnet
+    Relay {
+        /// Ports below 1024 need root or CAP_NET_BIND_SERVICE
+        #[arg(long, default_value = "0.0.0.0:9", value_name = "ADDR")]
+        listen: SocketAddr,
+    },
+    /// Show past wakes, oldest first
+    History {
+        /// Only show wakes of this machine
+        #[arg(long, short)]
+        machine: Option<String>,
+        /// Only show the most recent entries
+        #[arg(long, short = 'n', value_name = "COUNT")]
+        limit: Option<usize>,
+    },
+}
+
+pub fn run(command: Command, config_path: &Path) -> i32 {
+    // Commands that change the config keep other instances out until they are saved.
+    let _lock = match command {
+        Command::Add { .. } | Command::Remove { .. } | Command::Import { dry_run: false, .. } => {
+            match config::lock_config(config_path) {
+                Ok(lock) => Some(lock),
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    return EXIT_CONFIG;
+                }
+            }
+        }
+        _ => None,
+    };
+    let mut config = match config::read_config(config_path) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return EXIT_CONFIG;
+        }
+    };
+
+    match command {
+        Command::Wake { machines, group } => {
+            let mut code = 0;
+            let mut targets = machines;
+            for group in group {
+                let members: Vec<_> = config
+                    .machines
+                    .iter()
+                    .filter(|m| m.in_group(&group))
+                    .map(|m| m.name.clone())
+                    .collect();
+                if members.is_empty() {
+                    eprintln!("error: no machines in group {group}");
+                    code = EXIT_FAILURE;
+                }
+                targets.extend(members);
+            }
+            for arg in targets {
+                let machine = match config.machines.iter().find(|m| m.name == arg) {
+                    Some(machine) => machine.clone(),
+                    None => match arg.parse() {
+                        Ok(mac_address) => config::Machine {
+                            name: arg.clone(),
+                            mac_address,
+                            ..Default::default()
+                        },
+                        Err(_) => {
+                            eprintln!("error: no machine named {arg}");
+                            code = EXIT_FAILURE;
+                            continue;
+                        }
+                    },
+                };
+                let burst = wake::Burst::for_machine(&config, &machine);
+                let result = wake::send_burst(&machine, burst, |_| {});
+                let entry = history::Entry::new(&machine, "cli", &result);
+                if let Err(err) = history::append(&config::history_path(config_path), &entry) {
+                    eprintln!("warning: cannot record history: {err}");
+                }
+                match result {
+                    Ok(()) => println!(
+                        "sent {} wol packet(s) to {} ({})",
+                        burst.repeat, machine.name, machine.mac_address
+                    ),
+                    Err(err) => {
+                        eprintln!("error: cannot wake {}: {err}", machine.name);
+                        code = EXIT_FAILURE;
+                    }
+                }
+            }
+            code
+        }
+        Command::List => {
+            for machine in &config.machines {
+                println!(
+                    "{:<20}{:<20}{}",
+                    machine.name,
+                    machine.mac_address,
+                    machine.tags.join(",")
+                );
+            }
+            0
+        }
+        Command::Add { name, mac } => {
+            let mac_address = match mac.parse::<config::MacAddress>() {
+                Ok(mac_address) if mac_address.is_unicast() => mac_address,
+                Ok(_) => {
+                    eprintln!("error: {mac} is a broadcast, multicast or all-zero address");
+                    return EXIT_USAGE;
+                }
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    return EXIT_USAGE;
+                }
+            };
+            if config.machines.iter().any(|m| m.name == name) {
+                eprintln!("error: {name} already exists");
+                return EXIT_FAILURE;
+            }
+            if let Some(other) = config
+                .machines
+                .iter()
+                .find(|m| m.mac_address == mac_address)
+            {
+                eprintln!("warning: {} has the same MAC address", other.name);
+            }
+            config.machines.push(config::Machine {
+                name,
+                mac_address,
+                ..Default::default()
+            });
+            save(config_path, &config)
+        }
+        Command::Remove { name } => {
+            let Some(index) = config.machines.iter().position(|m| m.name == name) else {
+                eprintln!("error: no machine named {name}");
+                return EXIT_FAILURE;
+            };
+            config.machines.remove(index);
+            save(config_path, &config)
+        }
+        Command::Import {
+            path,
+            format,
+            mode,
+            dry_run,
+        } => {
+            let imported = match inventory::read_file(&path, format) {
+                Ok(imported) => imported,
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    return EXIT_FAILURE;
+                }
+            };
+            let plan = inventory::plan(&config.machines, imported, mode);
+            for name in &plan.added {
+                println!("add {name}");
+            }
+            for name in &plan.duplicates {
+                println!("skip {name}: MAC address already present");
+            }
+            for renamed in &plan.renamed {
+                println!(
+                    "rename {} to {}: name already taken",
+                    renamed.from, renamed.to
+                );
+            }
+            if mode == ImportMode::Replace {
+                let kept: Vec<_> = plan.machines.iter().map(|m| &m.name).collect();
+                for machine in config.machines.iter().filter(|m| !kept.contains(&&m.name)) {
+                    println!("remove {}", machine.name);
+                }
+            }
+            if dry_run {
+                return 0;
+            }
+            config.machines = plan.machines;
+            save(config_path, &config)
+        }
+        Command::Daemon => daemon::run(config_path),
+        Command::Serve { listen } => match serve::run(config_path, listen) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("error: {err}");
+                EXIT_FAILURE
+            }
+        },
+        Command::Mqtt => match mqtt::run(config_path) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("error: {err}");
+                EXIT_FAILURE
+            }
+        },
+        Command::Relay { listen } => match relay::run(config_path, listen) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("error: {err}");
+                EXIT_FAILURE
+            }
+        },
+        Command::History { machine, limit } => {
+            let entries = match history::read(&config::history_path(config_path)) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    return EXIT_FAILURE;
+                }
+            };
+            let entries: Vec<_> = entries
+                .iter()
+                .filter(|entry| machine.as_ref().is_none_or(|name| entry.machine == *name))
+                .collect();
+            let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+            for entry in &entries[skip..] {
+                println!("{}", entry.summary());
+            }
+            0
+        }
+        Command::Export { path, format } => {
+            let result = match &path {
+                Some(path) => inventory::write_file(path, format, &config.machines),
+                None => inventory::render(format.unwrap_or(Format::Ethers), &config.machines)
+                    .map(|contents| print!("{contents}")),
+            };
+            match result {
+                Ok(()) => 0,
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    EXIT_FAILURE
+                }
+            }
+        }
+    }
+}
+
+fn save(config_path: &Path, config: &config::Config) -> i32 {
+    match config::write_config(config_path, config) {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("error: {err}");
+            EXIT_CONFIG
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Local, Utc};
+use std::{env, path::Path, thread, time::Duration};
+
+use crate::app::{
+    config::{self, Config, Fingerprint},
+    history,
+    schedule::{self, Parsed},
+    wake,
+};
+
+/// Longest sleep between checks, so edits to the config are picked up.
+const MAX_SLEEP: Duration = Duration::from_secs(30);
+
+/// Runs the schedules of the config at `config_path` until killed.
+pub fn run(config_path: &Path) -> i32 {
+    let mut config = Config::default();
+    let mut fingerprint: Option<Fingerprint> = None;
+    let mut schedules: Vec<Option<Parsed>> = vec![];
+    let mut last_check = Utc::now();
+    info(&format!("watching schedules in {}", config_path.display()));
+
+    loop {
+        match config::load_config(config_path) {
+            Ok((loaded, loaded_fingerprint))
+                if fingerprint.as_ref() != Some(&loaded_fingerprint) =>
+            {
+                schedules = loaded
+                    .schedules
+                    .iter()
+                    .enumerate()
+                    .map(|(index, schedule)| match Parsed::new(schedule) {
+                        Ok(parsed) => Some(parsed),
+                        Err(err) => {
+                            error(&format!("skipping schedule {}: {err}", index + 1));
+                            None
+                        }
+                    })
+                    .collect();
+                config = loaded;
+                fingerprint = Some(loaded_fingerprint);
+                info(&format!("loaded {} schedule(s)", schedules.len()));
+            }
+            Ok(_) => {}
+            Err(err) => error(&format!(
+                "cannot reload config, keeping the last one: {err}"
+            )),
+        }
+
+        let now = Utc::now();
+        for (schedule, parsed) in config.schedules.iter().zip(&schedules) {
+            let Some(parsed) = parsed else {
+                continue;
+            };
+            if parsed
+                .next_after(last_check)
+                .is_some_and(|next| next <= now)
+            {
+                fire(&config, schedule, config_path);
+            }
+        }
+        last_check = now;
+
+        let next = schedules
+            .iter()
+            .flatten()
+            .filter_map(|parsed| parsed.next_after(now))
+            .min();
+        let sleep = next.map_or(MAX_SLEEP, |next| {
+            // Already due when the schedules took a while to send.
+            let until = (next - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+            until.min(MAX_SLEEP)
+        });
+        thread::sleep(sleep);
+    }
+}
+
+fn fire(config: &Config, schedule: &config::Schedule, config_path: &Path) {
+    let targets = schedule::targets(schedule, &config.machines);
+    if targets.is_empty() {
+        error(&format!(
+            "schedule for {} matches no machines",
+            schedule
+                .machine
+                .as_deref()
+                .or(schedule.group.as_deref())
+                .unwrap_or("nothing")
+        ));
+    }
+    for index in targets {
+        let machine = &config.machines[index];
+        let burst = wake::Burst::for_machine(config, machine);
+        let result = wake::send_burst(machine, burst, |_| {});
+        match &result {
+            Ok(()) => info(&format!(
+                "woke {} ({}) via {}",
+                machine.name,
+                machine.mac_address,
+                wake::target_label(machine)
+            )),
+            Err(err) => error(&format!("cannot wake {}: {err}", machine.name)),
+        }
+        let entry = history::Entry::new(machine, "daemon", &result);
+        if let Err(err) = history::append(&config::history_path(config_path), &entry) {
+            error(&format!("cannot record history: {err}"));
+        }
+    }
+}
+
+pub fn info(message: &str) {
+    log(6, message);
+}
+
+pub fn error(message: &str) {
+    log(3, message);
+}
+
+/// Prints with a syslog priority prefix when running under systemd, which adds its own
+/// timestamps, and with a timestamp otherwise.
+fn log(priority: u8, message: &str) {
+    if env::var_os("JOURNAL_STREAM").is_some() {
+        println!("<{priority}>{message}");
+    } else {
+        let now: DateTime<Local> = Local::now();
+        println!("{} {message}", now.format("%Y-%m-%d %H:%M:%S"));
+    }
+}
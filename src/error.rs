@@ -0,0 +1,47 @@
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The config file could not be located, read or written.
+    #[error("config {}: {source}", .path.display())]
+    Config {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The config file is not valid TOML or doesn't match the expected layout.
+    #[error(
+        "cannot parse config {} at line {line}, column {column}: {message}",
+        .path.display()
+    )]
+    ConfigParse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A file to import from or export to could not be read or written.
+    #[error("{}: {source}", .path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The config file changed on disk since it was loaded.
+    #[error("{} was changed by another program", .0.display())]
+    Conflict(PathBuf),
+    /// The config file was opened read-only because it could not be loaded.
+    #[error("refusing to overwrite {}, fix the config file and restart", .0.display())]
+    ReadOnly(PathBuf),
+    /// A value typed by the user or stored in the config is malformed.
+    #[error("{0}")]
+    Parse(String),
+    #[error("network error: {0}")]
+    Network(#[source] io::Error),
+    #[error("terminal error: {0}")]
+    Terminal(#[source] io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,42 +1,86 @@
 mod app;
+mod cli;
+mod daemon;
+mod error;
+mod mqtt;
+mod relay;
+mod serve;
 
-use std::{error::Error, io, time::Duration};
+use clap::Parser;
+use std::{
+    io::{self, Stdout},
+    panic,
+    path::PathBuf,
+    process,
+    time::Duration,
+};
 
 use crossterm::{
+    cursor::Show,
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
-use crate::app::App;
+use crate::{
+    app::{config, App},
+    cli::Cli,
+    error::{Error, Result},
+};
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+fn main() {
+    let cli = Cli::parse();
+    let config_path = match config::resolve_config_path(cli.config.as_deref()) {
+        Ok(path) => path,
+        Err(err) => {
+            eprintln!("error: {err}");
+            process::exit(cli::EXIT_CONFIG);
+        }
+    };
+    if let Some(command) = cli.command {
+        process::exit(cli::run(command, &config_path));
+    }
+
+    // restore the terminal before the panic message is printed
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
+
+    let res = run_tui(config_path);
+
+    // restore the terminal even when setting it up failed halfway
+    let restored = restore_terminal().map_err(Error::Terminal);
+
+    if let Err(err) = res.and(restored) {
+        eprintln!("error: {err}");
+        process::exit(cli::EXIT_FAILURE);
+    }
+}
+
+fn run_tui(config_path: PathBuf) -> Result<()> {
+    enable_raw_mode().map_err(Error::Terminal)?;
+    let mut terminal = setup_terminal().map_err(Error::Terminal)?;
 
-    // create app and run it
     let tick_rate = Duration::from_millis(250);
-    let app = App::new();
+    let app = App::new(config_path);
+    app::run_app(&mut terminal, app, tick_rate)
+}
 
-    let res = app::run_app(&mut terminal, app, tick_rate);
+fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    Terminal::new(CrosstermBackend::new(stdout))
+}
 
-    // restore terminal
+fn restore_terminal() -> io::Result<()> {
     disable_raw_mode()?;
     execute!(
-        terminal.backend_mut(),
+        io::stdout(),
         LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-
-    if let Err(err) = res {
-        println!("{err:?}");
-    }
-
-    Ok(())
+        DisableMouseCapture,
+        Show
+    )
 }
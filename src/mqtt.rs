@@ -0,0 +1,404 @@
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    app::{
+        config::{self, Config, Mqtt},
+        history, probe,
+        status::{self, StatusPoller},
+        wake,
+    },
+    daemon::{error, info},
+};
+
+pub const DEFAULT_PORT: u16 = 1883;
+pub const DEFAULT_PREFIX: &str = "woltui";
+
+/// How often machine states are compared with what was last published.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+/// Wait before polling the connection again after an error, which reconnects.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Machine states shared by the wake handlers and the state publisher. Every state goes
+/// out through `States::publish`, so a retained `waking` is always replaced later.
+#[derive(Default)]
+struct States {
+    /// Machines the state publisher probes. Only their `waking` is replaced later.
+    watched: HashSet<String>,
+    /// Machines woken over MQTT, with when, so they show as `waking` until their probe
+    /// sees them or the probe times out.
+    waking: HashMap<String, Instant>,
+    /// The retained state last published for each machine.
+    published: HashMap<String, &'static str>,
+}
+
+impl States {
+    fn publish(&mut self, client: &Client, prefix: &str, name: &str, state: &'static str) {
+        if self.published.get(name) != Some(&state) {
+            publish(client, &format!("{prefix}/{name}/state"), true, state);
+            self.published.insert(name.to_string(), state);
+        }
+    }
+}
+
+type SharedStates = Arc<Mutex<States>>;
+
+/// Connects to the broker in the config at `config_path`, wakes machines on
+/// `<prefix>/<machine>/wake` and publishes wake events on `<prefix>/<machine>/event`
+/// until killed. Machines with a probe also get their `<prefix>/<machine>/state`.
+pub fn run(config_path: &Path) -> Result<(), String> {
+    let config = config::read_config(config_path).map_err(|err| err.to_string())?;
+    let Some(settings) = config.mqtt.clone() else {
+        return Err(format!("no [mqtt] section in {}", config_path.display()));
+    };
+    let prefix = settings
+        .prefix
+        .clone()
+        .unwrap_or_else(|| DEFAULT_PREFIX.to_string());
+    let (client, mut connection) = Client::new(options(&settings, &prefix), 10);
+    let states: SharedStates = Arc::default();
+    spawn_state_publisher(&config, client.clone(), prefix.clone(), Arc::clone(&states));
+    info(&format!("connecting to mqtt://{}", settings.host));
+
+    for notification in connection.iter() {
+        match notification {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info("connected");
+                // The session isn't kept by the broker, so subscribe again on every connect.
+                let subscribed = client
+                    .subscribe(format!("{prefix}/+/wake"), QoS::AtLeastOnce)
+                    .and_then(|()| {
+                        client.publish(format!("{prefix}/status"), QoS::AtLeastOnce, true, "online")
+                    });
+                if let Err(err) = subscribed {
+                    error(&format!("cannot subscribe: {err}"));
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let Some(name) = publish
+                    .topic
+                    .strip_prefix(&format!("{prefix}/"))
+                    .and_then(|topic| topic.strip_suffix("/wake"))
+                else {
+                    continue;
+                };
+                // Waking blocks for the burst, and the connection has to keep being polled
+                // meanwhile for the client to publish.
+                let name = name.to_string();
+                let client = client.clone();
+                let prefix = prefix.clone();
+                let config_path = config_path.to_path_buf();
+                let states = Arc::clone(&states);
+                thread::spawn(move || handle_wake(&config_path, &client, &prefix, &name, &states));
+            }
+            Ok(_) => {}
+            Err(err) => {
+                error(&format!("connection to the broker failed: {err}"));
+                thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn options(settings: &Mqtt, prefix: &str) -> MqttOptions {
+    let client_id = settings
+        .client_id
+        .clone()
+        .unwrap_or_else(|| format!("woltui-{}", std::process::id()));
+    let mut options = MqttOptions::new(
+        client_id,
+        settings.host.clone(),
+        settings.port.unwrap_or(DEFAULT_PORT),
+    );
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &settings.username {
+        options.set_credentials(
+            username.clone(),
+            settings.password.clone().unwrap_or_default(),
+        );
+    }
+    options.set_last_will(LastWill::new(
+        format!("{prefix}/status"),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    options
+}
+
+/// Wakes the machine named `name`, reading the config fresh so machines added since
+/// startup can be woken too.
+fn handle_wake(
+    config_path: &Path,
+    client: &Client,
+    prefix: &str,
+    name: &str,
+    states: &SharedStates,
+) {
+    let config = match config::read_config(config_path) {
+        Ok(config) => config,
+        Err(err) => {
+            error(&format!("cannot read config: {err}"));
+            return;
+        }
+    };
+    let Some(machine) = config.machines.iter().find(|machine| machine.name == name) else {
+        error(&format!("ignoring wake of unknown machine {name}"));
+        return;
+    };
+
+    {
+        let mut states = states.lock().unwrap();
+        // Machines added since startup aren't probed, so nothing would replace `waking`.
+        if states.watched.contains(&machine.name) {
+            states.waking.insert(machine.name.clone(), Instant::now());
+            states.publish(client, prefix, &machine.name, "waking");
+        }
+    }
+    let burst = wake::Burst::for_machine(&config, machine);
+    let result = wake::send_burst(machine, burst, |_| {});
+    match &result {
+        Ok(()) => info(&format!(
+            "woke {} ({}) via {}",
+            machine.name,
+            machine.mac_address,
+            wake::target_label(machine)
+        )),
+        Err(err) => {
+            error(&format!("cannot wake {}: {err}", machine.name));
+            states.lock().unwrap().waking.remove(&machine.name);
+        }
+    }
+
+    let entry = history::Entry::new(machine, "mqtt", &result);
+    if let Err(err) = history::append(&config::history_path(config_path), &entry) {
+        error(&format!("cannot record history: {err}"));
+    }
+    match serde_json::to_string(&entry) {
+        Ok(event) => publish(client, &format!("{prefix}/{name}/event"), false, event),
+        Err(err) => error(&format!("cannot encode wake event: {err}")),
+    }
+}
+
+/// Publishes the state of each machine with a probe whenever it changes: `waking` from
+/// a wake until the probe sees the machine or times out, `online` or `offline` otherwise.
+fn spawn_state_publisher(config: &Config, client: Client, prefix: String, states: SharedStates) {
+    let interval = config
+        .status_interval_secs
+        .map_or(status::DEFAULT_INTERVAL, Duration::from_secs);
+    let machines: Vec<(String, Duration)> = config
+        .machines
+        .iter()
+        .filter_map(|machine| {
+            let timeout = machine
+                .probe
+                .as_ref()?
+                .timeout_secs
+                .map_or(probe::DEFAULT_TIMEOUT, Duration::from_secs);
+            Some((machine.name.clone(), timeout))
+        })
+        .collect();
+    let poller = StatusPoller::spawn(&config.machines, interval);
+    states.lock().unwrap().watched = machines.iter().map(|(name, _)| name.clone()).collect();
+
+    thread::spawn(move || loop {
+        for (name, timeout) in &machines {
+            let Some(status) = poller.status(name) else {
+                continue;
+            };
+            let mut states = states.lock().unwrap();
+            let state = if status.online {
+                states.waking.remove(name);
+                "online"
+            } else if states
+                .waking
+                .get(name)
+                .is_some_and(|since| since.elapsed() < *timeout)
+            {
+                "waking"
+            } else {
+                states.waking.remove(name);
+                "offline"
+            };
+            states.publish(&client, &prefix, name, state);
+        }
+        thread::sleep(PUBLISH_INTERVAL);
+    });
+}
+
+fn publish(client: &Client, topic: &str, retain: bool, payload: impl Into<Vec<u8>>) {
+    if let Err(err) = client.publish(topic, QoS::AtLeastOnce, retain, payload) {
+        error(&format!("cannot publish to {topic}: {err}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream, UdpSocket},
+    };
+
+    /// Reads one MQTT packet, returning its first header byte and the rest.
+    fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut byte = [0; 1];
+        stream.read_exact(&mut byte).unwrap();
+        let header = byte[0];
+        let mut len = 0;
+        let mut shift = 0;
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            len |= ((byte[0] & 0x7f) as usize) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).unwrap();
+        (header, body)
+    }
+
+    /// A QoS 0 PUBLISH with a body shorter than 128 bytes.
+    fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+        let mut body = (topic.len() as u16).to_be_bytes().to_vec();
+        body.extend_from_slice(topic.as_bytes());
+        body.extend_from_slice(payload);
+        let mut packet = vec![0x30, body.len() as u8];
+        packet.extend(body);
+        packet
+    }
+
+    /// Plays the broker for a single client: acknowledges what it sends and collects
+    /// its publishes as topic and payload until `until` returns true.
+    fn broker(
+        stream: &mut TcpStream,
+        mut on_subscribed: impl FnMut(&mut TcpStream),
+        until: impl Fn(&[(String, String)]) -> bool,
+    ) -> Vec<(String, String)> {
+        let mut published = vec![];
+        while !until(&published) {
+            let (header, body) = read_packet(stream);
+            match header >> 4 {
+                // CONNECT
+                1 => stream.write_all(&[0x20, 2, 0, 0]).unwrap(),
+                // PUBLISH
+                3 => {
+                    let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                    let mut payload = &body[2 + topic_len..];
+                    if (header >> 1) & 0x3 > 0 {
+                        stream
+                            .write_all(&[0x40, 2, payload[0], payload[1]])
+                            .unwrap();
+                        payload = &payload[2..];
+                    }
+                    published.push((topic, String::from_utf8_lossy(payload).into_owned()));
+                }
+                // SUBSCRIBE
+                8 => {
+                    stream.write_all(&[0x90, 3, body[0], body[1], 1]).unwrap();
+                    on_subscribed(stream);
+                }
+                // PINGREQ
+                12 => stream.write_all(&[0xd0, 0]).unwrap(),
+                _ => {}
+            }
+        }
+        published
+    }
+
+    #[test]
+    fn wake_command_sends_and_publishes_state_and_event() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = UdpSocket::bind("127.0.0.1:0").unwrap();
+        target
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let config_path = config::test_config_path(
+            "mqtt-wake",
+            &format!(
+                r#"
+[mqtt]
+host = "127.0.0.1"
+port = {}
+prefix = "lab"
+
+[[machines]]
+name = "nas"
+mac_address = "00:11:22:33:44:55"
+target = "127.0.0.1"
+port = {}
+
+# TEST-NET-1 never answers, so the machine stays waking.
+[machines.probe]
+host = "192.0.2.1"
+port = 9
+timeout_secs = 600
+"#,
+                listener.local_addr().unwrap().port(),
+                target.local_addr().unwrap().port(),
+            ),
+        );
+        let client_config_path = config_path.clone();
+        thread::spawn(move || run(&client_config_path));
+
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let published = broker(
+            &mut stream,
+            |stream| {
+                stream
+                    .write_all(&publish_packet("lab/nas/wake", b""))
+                    .unwrap()
+            },
+            |published| published.iter().any(|(topic, _)| topic == "lab/nas/event"),
+        );
+
+        let mut packet = [0; 128];
+        let (len, _) = target.recv_from(&mut packet).unwrap();
+        assert_eq!(
+            &packet[..len],
+            wake::magic_packet(&[0, 0x11, 0x22, 0x33, 0x44, 0x55], None)
+        );
+
+        assert!(published.contains(&("lab/status".into(), "online".into())));
+        assert!(published.contains(&("lab/nas/state".into(), "waking".into())));
+        let (_, event) = published
+            .iter()
+            .find(|(topic, _)| topic == "lab/nas/event")
+            .unwrap();
+        let event: history::Entry = serde_json::from_str(event).unwrap();
+        assert_eq!(event.machine, "nas");
+        assert_eq!(event.source, "mqtt");
+        assert_eq!(event.error, None);
+        assert_eq!(
+            history::read(&config::history_path(&config_path))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn waking_is_replaced_even_when_the_state_did_not_change_before() {
+        let (client, _connection) = Client::new(MqttOptions::new("test", "127.0.0.1", 1), 10);
+        let mut states = States::default();
+        states.publish(&client, "lab", "nas", "online");
+        states.publish(&client, "lab", "nas", "waking");
+        assert_eq!(states.published["nas"], "waking");
+        // The publisher would skip this if `waking` hadn't gone through `publish`.
+        states.publish(&client, "lab", "nas", "online");
+        assert_eq!(states.published["nas"], "online");
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::VecDeque,
+    net::{SocketAddr, UdpSocket},
+    path::Path,
+};
+
+use crate::{
+    app::{
+        config::{self, Machine},
+        history, wake,
+    },
+    daemon::{error, info},
+};
+
+/// How many of the relay's own recent source ports are remembered.
+const OWN_PORTS: usize = 16;
+
+/// Listens for magic packets on `listen` and sends them again in the local subnet. Only
+/// MACs of machines in the config at `config_path` are relayed, the way those machines
+/// are configured to be woken.
+pub fn run(config_path: &Path, listen: SocketAddr) -> Result<(), String> {
+    let socket =
+        UdpSocket::bind(listen).map_err(|err| format!("cannot listen on {listen}: {err}"))?;
+    info(&format!("relaying magic packets received on {listen}"));
+    relay(config_path, &socket);
+    Ok(())
+}
+
+fn relay(config_path: &Path, socket: &UdpSocket) {
+    // The relay's own broadcast comes back when it listens on the port it sends to. Each
+    // packet goes out from a fresh socket, so the echo is recognized by its source port.
+    let mut own_ports: VecDeque<u16> = VecDeque::with_capacity(OWN_PORTS);
+    let mut buffer = [0; 1500];
+    loop {
+        let (len, source) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err) => {
+                error(&format!("cannot receive: {err}"));
+                continue;
+            }
+        };
+        if own_ports.contains(&source.port()) {
+            continue;
+        }
+        let Some((mac, password)) = wake::parse_magic_packet(&buffer[..len]) else {
+            error(&format!(
+                "ignoring {len} bytes from {source}: not a magic packet"
+            ));
+            continue;
+        };
+
+        // Read fresh so machines added since the relay started are allowed.
+        let config = match config::read_config(config_path) {
+            Ok(config) => config,
+            Err(err) => {
+                error(&format!("cannot read config: {err}"));
+                continue;
+            }
+        };
+        let Some(machine) = config
+            .machines
+            .iter()
+            .find(|machine| machine.mac_address == mac)
+        else {
+            error(&format!(
+                "refusing to relay {mac} from {source}: not in the config"
+            ));
+            continue;
+        };
+        // Sending on through another relay could loop back here.
+        let local = Machine {
+            via: None,
+            ..machine.clone()
+        };
+        let packet = match password {
+            Some(password) => wake::magic_packet(&mac.0, Some(password)),
+            None => wake::packet_for(&local),
+        };
+        let result = wake::deliver(&local, &packet);
+        if let Ok(Some(port)) = result {
+            if own_ports.len() == OWN_PORTS {
+                own_ports.pop_front();
+            }
+            own_ports.push_back(port);
+        }
+        let result = result.map(|_| ());
+        match &result {
+            Ok(()) => info(&format!(
+                "relayed {} ({mac}) from {source} to {}",
+                local.name,
+                wake::target_label(&local)
+            )),
+            Err(err) => error(&format!("cannot relay {}: {err}", local.name)),
+        }
+        let entry = history::Entry::new(&local, "relay", &result);
+        if let Err(err) = history::append(&config::history_path(config_path), &entry) {
+            error(&format!("cannot record history: {err}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, thread, time::Duration};
+
+    const KNOWN: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const UNKNOWN: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x66];
+
+    /// Starts a relay on loopback for a config with one machine at `target_port`,
+    /// returning the config path and the relay's address.
+    fn start(name: &str, target_port: impl FnOnce(u16) -> u16) -> (PathBuf, SocketAddr) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let listen = socket.local_addr().unwrap();
+        let config_path = config::test_config_path(
+            name,
+            &format!(
+                r#"
+[[machines]]
+name = "nas"
+mac_address = "00:11:22:33:44:55"
+target = "127.0.0.1"
+port = {}
+"#,
+                target_port(listen.port())
+            ),
+        );
+        let relay_config_path = config_path.clone();
+        thread::spawn(move || relay(&relay_config_path, &socket));
+        (config_path, listen)
+    }
+
+    /// Waits until the history has `count` entries and returns them.
+    fn wait_for_history(config_path: &Path, count: usize) -> Vec<history::Entry> {
+        for _ in 0..100 {
+            let entries = history::read(&config::history_path(config_path)).unwrap();
+            if entries.len() >= count {
+                return entries;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("fewer than {count} history entries");
+    }
+
+    #[test]
+    fn only_allowed_macs_are_relayed() {
+        let target = UdpSocket::bind("127.0.0.1:0").unwrap();
+        target
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let target_port = target.local_addr().unwrap().port();
+        let (config_path, listen) = start("relay-allowlist", |_| target_port);
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(b"not a magic packet", listen).unwrap();
+        sender
+            .send_to(&wake::magic_packet(&UNKNOWN, None), listen)
+            .unwrap();
+        let password = [0xde, 0xad, 0xbe, 0xef];
+        sender
+            .send_to(&wake::magic_packet(&KNOWN, Some(&password)), listen)
+            .unwrap();
+
+        // The relay handles packets in order, so the first one out is for the known MAC.
+        let mut buffer = [0; 256];
+        let (len, _) = target.recv_from(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], wake::magic_packet(&KNOWN, Some(&password)));
+
+        let entries = wait_for_history(&config_path, 1);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].machine, "nas");
+        assert_eq!(entries[0].source, "relay");
+    }
+
+    #[test]
+    fn own_packets_are_not_relayed_again() {
+        // The machine's packets go to the relay itself, as with a broadcast on its port.
+        let (config_path, listen) = start("relay-echo", |listen_port| listen_port);
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender
+            .send_to(&wake::magic_packet(&KNOWN, None), listen)
+            .unwrap();
+
+        wait_for_history(&config_path, 1);
+        thread::sleep(Duration::from_millis(500));
+        let entries = history::read(&config::history_path(&config_path)).unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+}
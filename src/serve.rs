@@ -0,0 +1,433 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{net::SocketAddr, path::Path};
+
+use crate::{
+    app::{
+        config::{self, Config, MacAddress, Transport},
+        history, wake,
+    },
+    error::Error,
+};
+
+/// The parts of an HTTP request the API looks at, so it can be driven without a socket.
+pub struct ApiRequest<'a> {
+    pub method: &'a str,
+    /// Path with an optional query string, e.g. `/history?machine=nas`.
+    pub url: &'a str,
+    /// Value of the `Authorization` header.
+    pub authorization: Option<&'a str>,
+}
+
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    fn ok(body: Value) -> ApiResponse {
+        ApiResponse { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl ToString) -> ApiResponse {
+        ApiResponse {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+}
+
+/// A machine as listed by the API, without its SecureOn password.
+#[derive(Serialize)]
+struct MachineView<'a> {
+    name: &'a str,
+    mac: MacAddress,
+    target: String,
+    transport: Transport,
+    groups: &'a [String],
+}
+
+/// Serves the API on `listen` until killed. An `api_token` is required even on
+/// loopback, where any web page open in a local browser could otherwise wake machines.
+pub fn run(config_path: &Path, listen: SocketAddr) -> Result<(), String> {
+    let config = config::read_config(config_path).map_err(|err| err.to_string())?;
+    if api_token(&config).is_none() {
+        return Err(format!(
+            "refusing to serve without an api_token in {}",
+            config_path.display()
+        ));
+    }
+    let server = tiny_http::Server::http(listen).map_err(|err| err.to_string())?;
+    println!("listening on http://{listen}");
+
+    for request in server.incoming_requests() {
+        respond(config_path, request);
+    }
+    Ok(())
+}
+
+/// Answers a request received by tiny_http with `handle`.
+fn respond(config_path: &Path, mut request: tiny_http::Request) {
+    let authorization = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .map(|header| header.value.to_string());
+    // Bodies aren't used, but are read so keep-alive connections stay in sync.
+    let _ = std::io::copy(request.as_reader(), &mut std::io::sink());
+    let response = handle(
+        config_path,
+        &ApiRequest {
+            method: request.method().as_str(),
+            url: request.url(),
+            authorization: authorization.as_deref(),
+        },
+    );
+    let content_type =
+        tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+            .expect("static header is valid");
+    let _ = request.respond(
+        tiny_http::Response::from_string(response.body.to_string())
+            .with_status_code(response.status)
+            .with_header(content_type),
+    );
+}
+
+/// Answers one API request, reading the config at `config_path` fresh each time.
+pub fn handle(config_path: &Path, request: &ApiRequest) -> ApiResponse {
+    let config = match config::read_config(config_path) {
+        Ok(config) => config,
+        Err(err) => return ApiResponse::error(500, err),
+    };
+    // The config is read per request, so the token may have been removed since startup.
+    let Some(token) = api_token(&config) else {
+        return ApiResponse::error(401, "no api_token is configured");
+    };
+    let expected = format!("Bearer {token}");
+    if !request
+        .authorization
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), expected.as_bytes()))
+    {
+        return ApiResponse::error(401, "missing or invalid bearer token");
+    }
+
+    let (path, query) = request.url.split_once('?').unwrap_or((request.url, ""));
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    match (request.method, segments.as_slice()) {
+        ("GET", ["machines"]) => list_machines(&config),
+        ("POST", ["machines", name, "wake"]) => wake_machine(&config, config_path, name),
+        ("GET", ["history"]) => list_history(config_path, query),
+        (_, ["machines"]) | (_, ["machines", _, "wake"]) | (_, ["history"]) => {
+            ApiResponse::error(405, "method not allowed")
+        }
+        _ => ApiResponse::error(404, "not found"),
+    }
+}
+
+fn api_token(config: &Config) -> Option<&str> {
+    config
+        .api_token
+        .as_deref()
+        .filter(|token| !token.trim().is_empty())
+}
+
+fn list_machines(config: &Config) -> ApiResponse {
+    let machines: Vec<MachineView> = config
+        .machines
+        .iter()
+        .map(|machine| MachineView {
+            name: &machine.name,
+            mac: machine.mac_address,
+            target: wake::target_label(machine),
+            transport: machine.transport,
+            groups: &machine.tags,
+        })
+        .collect();
+    ApiResponse::ok(json!(machines))
+}
+
+fn wake_machine(config: &Config, config_path: &Path, name: &str) -> ApiResponse {
+    let Some(machine) = config.machines.iter().find(|machine| machine.name == name) else {
+        return ApiResponse::error(404, format!("no machine named {name}"));
+    };
+    let burst = wake::Burst::for_machine(config, machine);
+    let result = wake::send_burst(machine, burst, |_| {});
+    let entry = history::Entry::new(machine, "api", &result);
+    let _ = history::append(&config::history_path(config_path), &entry);
+    match result {
+        Ok(()) => ApiResponse::ok(json!({
+            "machine": machine.name,
+            "packets": burst.repeat,
+            "target": wake::target_label(machine),
+        })),
+        Err(err @ Error::Network(_)) => ApiResponse::error(502, err),
+        Err(err) => ApiResponse::error(500, err),
+    }
+}
+
+/// Newest first, narrowed by the `machine` and `limit` query parameters.
+fn list_history(config_path: &Path, query: &str) -> ApiResponse {
+    let mut machine = None;
+    let mut limit = None;
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        match key {
+            "machine" => machine = Some(percent_decode(&value.replace('+', " "))),
+            "limit" => match value.parse::<usize>() {
+                Ok(value) => limit = Some(value),
+                Err(_) => return ApiResponse::error(400, format!("invalid limit {value}")),
+            },
+            _ => {}
+        }
+    }
+
+    let entries = match history::read(&config::history_path(config_path)) {
+        Ok(entries) => entries,
+        Err(err) => return ApiResponse::error(500, err),
+    };
+    let entries: Vec<&history::Entry> = entries
+        .iter()
+        .rev()
+        .filter(|entry| machine.as_ref().is_none_or(|name| entry.machine == *name))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    ApiResponse::ok(json!(entries))
+}
+
+/// Decodes `%XX` escapes in a path segment or query value.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Compares without returning early, so response times don't reveal the token.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const CONFIG: &str = r#"
+api_token = "secret"
+
+[[machines]]
+name = "nas"
+mac_address = "00:11:22:33:44:55"
+secureon = "de:ad:be:ef"
+tags = ["rack"]
+
+[[machines]]
+name = "desktop"
+mac_address = "00:11:22:33:44:66"
+"#;
+
+    fn get(config_path: &Path, url: &str) -> ApiResponse {
+        request(config_path, "GET", url, Some("Bearer secret"))
+    }
+
+    fn request(
+        config_path: &Path,
+        method: &str,
+        url: &str,
+        authorization: Option<&str>,
+    ) -> ApiResponse {
+        handle(
+            config_path,
+            &ApiRequest {
+                method,
+                url,
+                authorization,
+            },
+        )
+    }
+
+    fn with_history(name: &str) -> PathBuf {
+        let config_path = config::test_config_path(name, CONFIG);
+        let config = config::read_config(&config_path).unwrap();
+        let history_path = config::history_path(&config_path);
+        for (machine, result) in [
+            (&config.machines[0], Ok(())),
+            (&config.machines[1], Ok(())),
+            (&config.machines[0], Err(Error::Parse("boom".into()))),
+        ] {
+            history::append(
+                &history_path,
+                &history::Entry::new(machine, "test", &result),
+            )
+            .unwrap();
+        }
+        config_path
+    }
+
+    #[test]
+    fn missing_or_wrong_token_is_unauthorized() {
+        let config_path = config::test_config_path("serve-auth", CONFIG);
+        for authorization in [None, Some("Bearer wrong"), Some("secret"), Some("Bearer ")] {
+            let response = request(&config_path, "GET", "/machines", authorization);
+            assert_eq!(response.status, 401);
+            assert!(response.body["error"].is_string());
+        }
+    }
+
+    #[test]
+    fn requests_without_a_configured_token_are_unauthorized() {
+        let config_path = config::test_config_path(
+            "serve-no-token",
+            "[[machines]]\nname = \"nas\"\nmac_address = \"00:11:22:33:44:55\"\n",
+        );
+        for authorization in [None, Some("Bearer "), Some("Bearer secret")] {
+            assert_eq!(
+                request(&config_path, "GET", "/machines", authorization).status,
+                401
+            );
+        }
+        assert!(run(&config_path, "127.0.0.1:0".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn machines_are_listed_without_secureon() {
+        let config_path = config::test_config_path("serve-machines", CONFIG);
+        let response = get(&config_path, "/machines");
+        assert_eq!(response.status, 200);
+
+        let machines = response.body.as_array().unwrap();
+        assert_eq!(machines.len(), 2);
+        assert_eq!(machines[0]["name"], "nas");
+        assert_eq!(machines[0]["mac"], "00:11:22:33:44:55");
+        assert_eq!(machines[0]["groups"], json!(["rack"]));
+        let listed = response.body.to_string();
+        assert!(!listed.contains("secureon"));
+        assert!(!listed.contains("de:ad:be:ef"));
+    }
+
+    #[test]
+    fn unknown_paths_and_methods_get_json_errors() {
+        let config_path = config::test_config_path("serve-errors", CONFIG);
+
+        let response = get(&config_path, "/nothing");
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, json!({ "error": "not found" }));
+
+        let response = request(&config_path, "DELETE", "/machines", Some("Bearer secret"));
+        assert_eq!(response.status, 405);
+        assert_eq!(response.body, json!({ "error": "method not allowed" }));
+
+        let response = get(&config_path, "/machines/nas/wake");
+        assert_eq!(response.status, 405);
+
+        let response = request(
+            &config_path,
+            "POST",
+            "/machines/missing/wake",
+            Some("Bearer secret"),
+        );
+        assert_eq!(response.status, 404);
+        assert_eq!(
+            response.body,
+            json!({ "error": "no machine named missing" })
+        );
+    }
+
+    #[test]
+    fn history_is_newest_first_and_filtered() {
+        let config_path = with_history("serve-history");
+
+        let response = get(&config_path, "/history");
+        assert_eq!(response.status, 200);
+        let entries = response.body.as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0]["error"], "boom");
+        assert_eq!(entries[1]["machine"], "desktop");
+
+        let response = get(&config_path, "/history?machine=nas&limit=1");
+        let entries = response.body.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["machine"], "nas");
+        assert!(entries[0]["error"].is_string());
+
+        let response = get(&config_path, "/history?machine=n%61s");
+        assert_eq!(response.body.as_array().unwrap().len(), 2);
+
+        let response = get(&config_path, "/history?limit=lots");
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn percent_decode_leaves_invalid_escapes() {
+        assert_eq!(percent_decode("a%20b"), "a b");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+
+    /// Sends `raw` to a server answering a single request with `respond`.
+    fn over_http(config_path: &Path, raw: &str) -> String {
+        use std::{
+            io::{Read, Write},
+            net::TcpStream,
+            thread,
+        };
+
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        let config_path = config_path.to_path_buf();
+        let serving = thread::spawn(move || respond(&config_path, server.recv().unwrap()));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        serving.join().unwrap();
+        response
+    }
+
+    #[test]
+    fn http_layer_reads_the_token_and_answers_json() {
+        let config_path = config::test_config_path("serve-http", CONFIG);
+
+        let response = over_http(
+            &config_path,
+            "GET /machines HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 401 "), "{response}");
+        assert!(response.contains("Content-Type: application/json"));
+        assert!(response.contains("missing or invalid bearer token"));
+
+        let response = over_http(
+            &config_path,
+            "GET /machines HTTP/1.1\r\nHost: localhost\r\n\
+             Authorization: Bearer secret\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 200 "), "{response}");
+        assert!(response.contains("Content-Type: application/json"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body[0]["name"], "nas");
+        assert_eq!(body[1]["name"], "desktop");
+    }
+}